@@ -0,0 +1,138 @@
+// Cycle detection for anything that is repeatedly stepped from one state to the next.
+//
+// States are compared through a key function, so a caller can compare whole states,
+// a compact form of them, or a hash of them when states are large.
+
+pub struct Cycle<S> {
+    // Steps taken before the cycle is entered
+    pub start: usize,
+    pub len: usize,
+    // The state after `start` steps
+    pub start_state: S,
+}
+
+// Brent's algorithm: only two states are ever held, and each step is taken a small number of times
+pub fn find_cycle<S, K>(initial: &S, mut step: impl FnMut(&mut S), key: impl Fn(&S) -> K) -> Cycle<S>
+where
+    S: Clone,
+    K: PartialEq,
+{
+    // Find the cycle length by racing the hare ahead of a tortoise that teleports to it at each power of two
+    let mut power = 1;
+    let mut len = 1;
+    let mut tortoise_key = key(initial);
+    let mut hare = initial.clone();
+    step(&mut hare);
+
+    while tortoise_key != key(&hare) {
+        if power == len {
+            tortoise_key = key(&hare);
+            power *= 2;
+            len = 0;
+        }
+
+        step(&mut hare);
+        len += 1;
+    }
+
+    // With the hare a cycle length ahead, they first meet where the cycle starts
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    for _ in 0..len {
+        step(&mut hare);
+    }
+
+    let mut start = 0;
+    while key(&tortoise) != key(&hare) {
+        step(&mut tortoise);
+        step(&mut hare);
+        start += 1;
+    }
+
+    Cycle {
+        start,
+        len,
+        start_state: tortoise,
+    }
+}
+
+// The state after `n` steps, skipping whole laps of the cycle
+pub fn state_after<S, K>(initial: S, n: usize, mut step: impl FnMut(&mut S), key: impl Fn(&S) -> K) -> S
+where
+    S: Clone,
+    K: PartialEq,
+{
+    let cycle = find_cycle(&initial, &mut step, key);
+
+    let (mut state, remaining) = if n < cycle.start {
+        (initial, n)
+    } else {
+        (cycle.start_state, (n - cycle.start) % cycle.len)
+    };
+
+    for _ in 0..remaining {
+        step(&mut state);
+    }
+
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each state steps to next[state]
+    fn stepper(next: &'static [usize]) -> impl FnMut(&mut usize) {
+        move |state| *state = next[*state]
+    }
+
+    fn naive(next: &'static [usize], n: usize) -> usize {
+        let mut state = 0;
+        for _ in 0..n {
+            state = next[state];
+        }
+        state
+    }
+
+    // 0 -> 1 -> 2 -> 3 -> 4 -> 2, so two steps before a cycle of three
+    const RHO: &[usize] = &[1, 2, 3, 4, 2];
+    // 0 -> 1 -> 2 -> 3 -> 3, so three steps before a state that steps to itself
+    const FIXED_POINT: &[usize] = &[1, 2, 3, 3];
+    // 0 -> 1 -> 2 -> 0, cycling from the start
+    const LOOP: &[usize] = &[1, 2, 0];
+
+    #[test]
+    fn finds_pre_period_and_length() {
+        for (next, start, len, start_state) in [(RHO, 2, 3, 2), (FIXED_POINT, 3, 1, 3), (LOOP, 0, 3, 0)] {
+            let cycle = find_cycle(&0, stepper(next), |&s| s);
+
+            assert_eq!((cycle.start, cycle.len, cycle.start_state), (start, len, start_state));
+        }
+    }
+
+    #[test]
+    fn state_after_matches_stepping() {
+        for next in [RHO, FIXED_POINT, LOOP] {
+            let start = find_cycle(&0, stepper(next), |&s| s).start;
+
+            // Before the cycle, exactly at its start, and many laps into it
+            for n in (0..start).chain([start, start + 1, 1000, 1001, 1002]) {
+                assert_eq!(state_after(0, n, stepper(next), |&s| s), naive(next, n), "n = {}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn compares_by_key() {
+        // The state carries a step counter, which the key ignores
+        let step = |state: &mut (usize, usize)| *state = (RHO[state.0], state.1 + 1);
+        let cycle = find_cycle(&(0, 0), step, |state| state.0);
+
+        assert_eq!((cycle.start, cycle.len), (2, 3));
+        assert_eq!(cycle.start_state, (2, 2));
+
+        // Before the cycle the counter is exact, after it the counter is wound back to within the first lap
+        assert_eq!(state_after((0, 0), 1, step, |state| state.0), (1, 1));
+        assert_eq!(state_after((0, 0), 1000, step, |state| state.0), (naive(RHO, 1000), 2 + (1000 - 2) % 3));
+    }
+}
@@ -34,34 +34,44 @@ pub use day04::part1 as day4_part1;
 pub use day04::part2 as day4_part2;
 pub use day05::part1 as day5_part1;
 pub use day05::part2 as day5_part2;
+pub use day05::report as day5_report;
 pub use day06::part1 as day6_part1;
 pub use day06::part2 as day6_part2;
 pub use day07::part1 as day7_part1;
 pub use day07::part2 as day7_part2;
+pub use day07::report as day7_report;
 pub use day08::part1 as day8_part1;
 pub use day08::part2 as day8_part2;
 pub use day09::part1 as day9_part1;
 pub use day09::part2 as day9_part2;
 pub use day10::part1 as day10_part1;
 pub use day10::part2 as day10_part2;
+pub use day10::report as day10_report;
 pub use day11::part1 as day11_part1;
 pub use day11::part2 as day11_part2;
+pub use day11::report as day11_report;
 pub use day12::part1 as day12_part1;
 pub use day12::part2 as day12_part2;
+pub use day12::report as day12_report;
 pub use day13::part1 as day13_part1;
 pub use day13::part2 as day13_part2;
+pub use day13::report as day13_report;
 pub use day14::part1 as day14_part1;
 pub use day14::part2 as day14_part2;
 pub use day15::part1 as day15_part1;
 pub use day15::part2 as day15_part2;
+pub use day15::report as day15_report;
 pub use day16::part1 as day16_part1;
 pub use day16::part2 as day16_part2;
+pub use day16::report as day16_report;
 pub use day17::part1 as day17_part1;
 pub use day17::part2 as day17_part2;
+pub use day17::report as day17_report;
 pub use day18::part1 as day18_part1;
 pub use day18::part2 as day18_part2;
 pub use day19::part1 as day19_part1;
 pub use day19::part2 as day19_part2;
+pub use day19::report as day19_report;
 pub use day20::part1 as day20_part1;
 pub use day20::part2 as day20_part2;
 pub use day21::part1 as day21_part1;
@@ -73,4 +83,15 @@ pub use day23::part2 as day23_part2;
 pub use day24::part1 as day24_part1;
 pub use day24::part2 as day24_part2;
 pub use day25::part1 as day25_part1;
-pub use day25::part2 as day25_part2;
\ No newline at end of file
+pub use day25::part2 as day25_part2;
+
+// Reports go to the terminal unless given a file
+fn print_or_write(out: &str, path: Option<&str>) -> Result<(), String> {
+    match path {
+        Some(path) => std::fs::write(path, out).map_err(|e| e.to_string()),
+        None => {
+            print!("{}", out);
+            Ok(())
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 const INPUT: &str = include_str!("input/day05.txt");
 
@@ -8,20 +8,39 @@ struct Almanac {
 }
 
 impl FromStr for Almanac {
-    type Err = &'static str;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let map_sets : Vec<_> = s.split("\r\n\r\n")
-            .map(|m_str| MappingSet::from_str(m_str))
-            .collect();
-
-        if let Some(Err(e)) = map_sets.iter().find(|m| m.is_err()) {
-            return Err(e);
+        let mut unchained = s.split("\n\n")
+            .map(MappingSet::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Order the MappingSets by following the categories from seed to location
+        let mut map_sets = Vec::new();
+        let mut category = "seed".to_string();
+
+        while category != "location" {
+            let from_category: Vec<_> = unchained.iter()
+                .enumerate()
+                .filter(|(_, m)| m.source == category)
+                .map(|(i, _)| i)
+                .collect();
+
+            let next_i = match from_category[..] {
+                [i] => i,
+                [] => return Err(format!("No map from {} found", category)),
+                _ => return Err(format!("Multiple maps from {} found", category))
+            };
+
+            let map_set = unchained.remove(next_i);
+            category = map_set.destination.clone();
+            map_sets.push(map_set);
         }
 
-        let map_sets : Vec<_> = map_sets.into_iter()
-                                        .map(|m| m.unwrap())
-                                        .collect();
+        if !unchained.is_empty() {
+            let names: Vec<_> = unchained.iter().map(|m| m.name()).collect();
+            return Err(format!("Maps not on the chain from seed to location: {}", names.join(", ")));
+        }
 
         Ok(Self { map_sets })
     }
@@ -45,35 +64,88 @@ impl Almanac {
 
         seed_ranges
     }
+
+    fn warnings(&self) -> Vec<String> {
+        self.map_sets.iter()
+            .flat_map(|map_set| map_set.overlaps().into_iter().map(move |(first, second)| format!(
+                "{} map has overlapping source ranges [{}, {}) and [{}, {}), the first listed is used",
+                map_set.name(), 
+                first.source, first.source + first.range, 
+                second.source, second.source + second.range)))
+            .collect()
+    }
+
+    // Collapse every MappingSet into a single seed -> location map
+    fn compose(&self) -> PiecewiseMap {
+        self.map_sets.iter()
+            .map(PiecewiseMap::from_mapping_set)
+            .fold(PiecewiseMap::identity(), |composed, map| composed.then(&map))
+    }
 }
 
 #[derive(Debug)]
 struct MappingSet {
+    source: String,
+    destination: String,
     mappings: Vec<Mapping>
 }
 
 impl FromStr for MappingSet {
-    type Err = &'static str;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mappings: Vec<_> = s.lines()
-                                .skip(1)
-                                .map(|m_str| Mapping::from_str(m_str))
-                                .collect();
+        let mut lines = s.lines();
 
-        if let Some(Err(e)) = mappings.iter().find(|m| m.is_err()) {
-            return Err(e);
-        }
+        let header = lines.next().unwrap_or_default();
 
-        let mappings: Vec<_> = mappings.into_iter()
-                                .map(|m| m.unwrap())
-                                .collect();
+        let Some((source, destination)) = header.strip_suffix(" map:").and_then(|n| n.split_once("-to-")) else {
+            return Err(format!("Failed to parse map header: {}", header));
+        };
 
-        Ok(Self{mappings})
+        let mappings = lines.map(Mapping::from_str)
+                            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            mappings
+        })
     }
 }
 
 impl MappingSet {
+    fn name(&self) -> String {
+        format!("{}-to-{}", self.source, self.destination)
+    }
+
+    // Pairs of mappings whose source ranges overlap, in the order they are listed
+    fn overlaps(&self) -> Vec<(&Mapping, &Mapping)> {
+        self.mappings.iter()
+            .enumerate()
+            .flat_map(|(i, first)| self.mappings[i + 1..].iter().map(move |second| (first, second)))
+            .filter(|(first, second)| first.source < second.source + second.range && second.source < first.source + first.range)
+            .collect()
+    }
+
+    // Source ranges between the lowest and highest mapped numbers that pass through unchanged
+    fn gaps(&self) -> Vec<NumRange> {
+        let mut sources: Vec<_> = self.mappings.iter().map(|m| (m.source, m.source + m.range)).collect();
+        sources.sort();
+
+        let mut gaps = Vec::new();
+        let mut covered_to = sources.first().map_or(0, |s| s.0);
+
+        for (start, end) in sources {
+            if start > covered_to {
+                gaps.push(NumRange { start: covered_to, len: start - covered_to });
+            }
+
+            covered_to = covered_to.max(end);
+        }
+
+        gaps
+    }
+
     fn apply(&self, input: i64) -> i64 {
         let dests : Vec<_> = self.mappings.iter()
             .filter_map(|m| {
@@ -99,7 +171,7 @@ impl MappingSet {
             let mut new_modified_ranges;
             (ranges, new_modified_ranges) = mapping.apply_to_ranges(ranges);
 
-            if new_modified_ranges.len() > 0 {
+            if !new_modified_ranges.is_empty() {
                 modified_ranges.append(&mut new_modified_ranges);
             }
         }
@@ -118,28 +190,23 @@ struct Mapping {
 }
 
 impl FromStr for Mapping {
-    type Err = &'static str;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let nums : Vec<_> = s.split_ascii_whitespace()
+                             .map(|n| n.parse::<i64>())
                              .collect();
 
-        let dest_start = nums[0].parse::<i64>();
-        let source_start = nums[1].parse::<i64>();
-        let range = nums[2].parse::<i64>();
-
-        if dest_start.is_err() | source_start.is_err() | range.is_err() {
-            return Err(stringify!(format!("Failed to parse mapping: {}", s)));
-        }
+        let [Ok(dest_start), Ok(source_start), Ok(range)] = nums[..] else {
+            return Err(format!("Failed to parse mapping: {}", s));
+        };
 
-        let source_start = source_start.unwrap();
-        let offset = dest_start.unwrap() - source_start;
-        let range = range.unwrap();
+        let offset = dest_start - source_start;
 
         Ok(Self { 
             source: source_start, 
-            offset: offset, 
-            range: range 
+            offset, 
+            range 
         })
     }
 }
@@ -175,6 +242,146 @@ impl Mapping {
     }
 }
 
+// Everything at or above this is left unchanged by a PiecewiseMap
+const DOMAIN_END: i64 = i64::MAX / 4;
+
+// A map from [0, DOMAIN_END) to itself made of pieces that each shift their inputs by a fixed offset.
+// The pieces are sorted by source, don't overlap and cover the whole domain.
+#[derive(Debug)]
+struct PiecewiseMap {
+    pieces: Vec<Mapping>
+}
+
+impl PiecewiseMap {
+    fn identity() -> Self {
+        Self { 
+            pieces: vec![Mapping { source: 0, offset: 0, range: DOMAIN_END }] 
+        }
+    }
+
+    fn from_mapping_set(map_set: &MappingSet) -> Self {
+        let mut pieces: Vec<Mapping> = Vec::new();
+
+        // Earlier mappings take priority, matching MappingSet::apply
+        for mapping in &map_set.mappings {
+            let mut uncovered = vec![(mapping.source, mapping.source + mapping.range)];
+
+            for piece in &pieces {
+                uncovered = uncovered.into_iter()
+                    .flat_map(|(start, end)| {
+                        let piece_end = piece.source + piece.range;
+                        [(start, end.min(piece.source)), (start.max(piece_end), end)]
+                    })
+                    .filter(|(start, end)| start < end)
+                    .collect();
+            }
+
+            pieces.extend(uncovered.into_iter().map(|(start, end)| Mapping { 
+                source: start, 
+                offset: mapping.offset, 
+                range: end - start 
+            }));
+        }
+
+        Self::from_pieces(pieces)
+    }
+
+    // Sort the pieces, fill any gaps with the identity and merge neighbours with the same offset
+    fn from_pieces(mut pieces: Vec<Mapping>) -> Self {
+        pieces.sort_by_key(|p| p.source);
+
+        let mut filled: Vec<Mapping> = Vec::new();
+        let mut next_start = 0;
+
+        for piece in pieces.into_iter().chain([Mapping { source: DOMAIN_END, offset: 0, range: 0 }]) {
+            if piece.source > next_start {
+                filled.push(Mapping { source: next_start, offset: 0, range: piece.source - next_start });
+            }
+
+            next_start = piece.source + piece.range;
+
+            if piece.range > 0 {
+                filled.push(piece);
+            }
+        }
+
+        let mut merged: Vec<Mapping> = Vec::new();
+
+        for piece in filled {
+            match merged.last_mut() {
+                Some(last) if last.offset == piece.offset => last.range += piece.range,
+                _ => merged.push(piece)
+            }
+        }
+
+        Self { pieces: merged }
+    }
+
+    // The map that applies self and then next
+    fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let mut pieces = Vec::new();
+
+        for piece in &self.pieces {
+            let image_start = piece.source + piece.offset;
+            let image_end = image_start + piece.range;
+
+            for next_piece in &next.pieces {
+                let start = image_start.max(next_piece.source);
+                let end = image_end.min(next_piece.source + next_piece.range);
+
+                if start < end {
+                    pieces.push(Mapping { 
+                        source: start - piece.offset, 
+                        offset: piece.offset + next_piece.offset, 
+                        range: end - start 
+                    });
+                }
+            }
+        }
+
+        Self::from_pieces(pieces)
+    }
+
+    fn apply(&self, input: i64) -> i64 {
+        let i = self.pieces.partition_point(|p| p.source + p.range <= input);
+
+        match self.pieces.get(i) {
+            Some(piece) => piece.apply(input),
+            None => input
+        }
+    }
+
+    // Every input that maps to the given output
+    fn preimages(&self, output: i64) -> Vec<i64> {
+        self.pieces.iter()
+            .filter(|p| p.source <= output - p.offset && output - p.offset < p.source + p.range)
+            .map(|p| output - p.offset)
+            .collect()
+    }
+
+    fn min_over(&self, range: &NumRange) -> Option<i64> {
+        let range_end = range.start + range.len;
+
+        self.pieces.iter()
+            .filter(|p| p.source < range_end && range.start < p.source + p.range)
+            .map(|p| range.start.max(p.source) + p.offset)
+            .min()
+    }
+}
+
+impl fmt::Display for PiecewiseMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for piece in &self.pieces {
+            let end = piece.source + piece.range;
+
+            writeln!(f, "[{}, {}) -> [{}, {}) ({:+})", 
+                piece.source, end, piece.source + piece.offset, end + piece.offset, piece.offset)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 struct NumRange {
     start: i64,
@@ -236,46 +443,161 @@ impl NumRange {
     }
 }
 
+fn get_almanac(maps_str: &str) -> Almanac {
+    let almanac = match Almanac::from_str(maps_str) {
+        Ok(almanac) => almanac,
+        Err(e) => panic!("Invalid almanac: {}", e)
+    };
+
+    for warning in almanac.warnings() {
+        println!("WARNING: {}", warning);
+    }
+
+    almanac
+}
+
+// Seed numbers read in pairs of start and length
+fn get_seed_ranges(seeds_str: &str) -> Vec<NumRange> {
+    let seed_nums : Vec<_> = seeds_str.split_ascii_whitespace().filter_map(|s| s.parse::<i64>().ok()).collect();
+
+    seed_nums.chunks_exact(2)
+        .map(|pair| NumRange { start: pair[0], len: pair[1] })
+        .collect()
+}
+
 pub fn part1() -> i64 {
-    let Some((seeds_str, maps_str)) = INPUT.split_once("\r\n\r\n") else {
+    let input = INPUT.replace("\r\n", "\n");
+
+    let Some((seeds_str, maps_str)) = input.split_once("\n\n") else {
         return 0;
     };
 
     let seeds : Vec<_> = seeds_str.split_ascii_whitespace().filter_map(|s| s.parse::<i64>().ok()).collect();
 
-    let almanac = Almanac::from_str(maps_str).unwrap();
+    let almanac = get_almanac(maps_str);
 
     let min_location = seeds.iter().map(|&s| almanac.get_location(s)).min();
 
+    if cfg!(debug_assertions) {
+        let seed_to_location = almanac.compose();
+        debug_assert_eq!(min_location, seeds.iter().map(|&s| seed_to_location.apply(s)).min());
+    }
+
     min_location.unwrap_or(0)
 }
 
 pub fn part2() -> i64 {
-    let Some((seeds_str, maps_str)) = INPUT.split_once("\r\n\r\n") else {
+    let input = INPUT.replace("\r\n", "\n");
+
+    let Some((seeds_str, maps_str)) = input.split_once("\n\n") else {
         return 0;
     };
 
-    let seed_nums : Vec<_> = seeds_str.split_ascii_whitespace().filter_map(|s| s.parse::<i64>().ok()).collect();
+    let seed_ranges = get_seed_ranges(seeds_str);
+    
+    let almanac = get_almanac(maps_str);
 
-    let mut seed_ranges = Vec::new();
+    let seed_to_location = almanac.compose();
 
-    for seed_start_i in (0..seed_nums.len()).step_by(2) {
-        let seed_start = seed_nums[seed_start_i];
-        let seed_range = seed_nums[seed_start_i + 1]; 
+    let min_loc = seed_ranges.iter()
+        .filter_map(|r| seed_to_location.min_over(r))
+        .min()
+        .unwrap();
 
-        seed_ranges.push(
-            NumRange {
-                start: seed_start,
-                len: seed_range
-            }
-        )
+    // Cross check against pushing the ranges through each MappingSet in turn
+    debug_assert_eq!(Some(min_loc), almanac.get_location_range(seed_ranges.clone()).iter().map(|r| r.start).min());
+    debug_assert!(seed_to_location.preimages(min_loc).iter()
+        .any(|&seed| seed_ranges.iter().any(|r| r.start <= seed && seed < r.start + r.len)));
+
+    min_loc
+}
+
+// Gaps in each map, the composed seed to location map, and which seeds give the lowest location in part 2
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    let input = INPUT.replace("\r\n", "\n");
+
+    let Some((seeds_str, maps_str)) = input.split_once("\n\n") else {
+        return Err("Failed to find seeds".to_string());
+    };
+
+    let seed_ranges = get_seed_ranges(seeds_str);
+    let almanac = get_almanac(maps_str);
+    let seed_to_location = almanac.compose();
+
+    let mut out = String::from("Unmapped ranges, which pass through unchanged:\n");
+
+    for map_set in &almanac.map_sets {
+        for gap in map_set.gaps() {
+            out.push_str(&format!("{}: [{}, {})\n", map_set.name(), gap.start, gap.start + gap.len));
+        }
     }
-    
-    let almanac = Almanac::from_str(maps_str).unwrap();
 
-    let loc_ranges = almanac.get_location_range(seed_ranges);
+    out.push_str(&format!("\nSeed to location:\n{}", seed_to_location));
+
+    if let Some(min_loc) = seed_ranges.iter().filter_map(|r| seed_to_location.min_over(r)).min() {
+        let seeds: Vec<_> = seed_to_location.preimages(min_loc)
+            .into_iter()
+            .filter(|&seed| seed_ranges.iter().any(|r| r.start <= seed && seed < r.start + r.len))
+            .map(|seed| seed.to_string())
+            .collect();
+
+        out.push_str(&format!("Lowest location {} comes from seed {}\n", min_loc, seeds.join(", ")));
+    }
+
+    super::print_or_write(&out, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("input/day05_example.txt");
 
-    let min_loc = loc_ranges.into_iter().min_by_key(|r| r.start);
+    // The example's maps, without the seeds
+    fn example_maps() -> Vec<String> {
+        EXAMPLE.replace("\r\n", "\n")
+            .split("\n\n")
+            .skip(1)
+            .map(|m| m.trim().to_string())
+            .collect()
+    }
+
+    fn parse_error(maps: &[String]) -> String {
+        Almanac::from_str(&maps.join("\n\n")).map(|_| ()).unwrap_err()
+    }
+
+    #[test]
+    fn maps_in_any_order() {
+        let mut maps = example_maps();
+        maps.reverse();
+
+        let almanac = Almanac::from_str(&maps.join("\n\n")).unwrap();
+
+        assert_eq!(almanac.map_sets.first().map(|m| m.name()), Some("seed-to-soil".to_string()));
+        assert_eq!([79, 14, 55, 13].map(|s| almanac.get_location(s)), [82, 43, 86, 35]);
+    }
+
+    #[test]
+    fn duplicate_map() {
+        let mut maps = example_maps();
+        maps.push(maps[0].replace("seed-to-soil", "seed-to-water"));
 
-    min_loc.unwrap().start
-}
\ No newline at end of file
+        assert_eq!(parse_error(&maps), "Multiple maps from seed found");
+    }
+
+    #[test]
+    fn missing_map() {
+        let mut maps = example_maps();
+        maps.remove(3);
+
+        assert_eq!(parse_error(&maps), "No map from water found");
+    }
+
+    #[test]
+    fn mislabelled_map() {
+        let mut maps = example_maps();
+        maps.push(maps[0].replace("seed-to-soil", "dirt-to-soil"));
+
+        assert_eq!(parse_error(&maps), "Maps not on the chain from seed to location: dirt-to-soil");
+    }
+}
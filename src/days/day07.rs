@@ -1,275 +1,225 @@
-use std::{str::FromStr, collections::HashMap};
+use std::fmt;
 use itertools::Itertools;
 
 const INPUT : &str = include_str!("input/day07.txt");
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
-enum HandType {
-    FiveOfAKind = 6,
-    FourOfAKind = 5,
-    FullHouse = 4,
-    ThreeOfAKind = 3,
-    TwoPair = 2,
-    OnePair = 1,
-    HighCard = 0
+// The shape of a hand: how many of each card it holds, largest group first.
+// Comparing these lexicographically ranks hands of any length, e.g. [5] > [4, 1] > [3, 2] > [3, 1, 1].
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+struct HandType {
+    group_sizes: Vec<usize>
 }
 
-impl FromStr for HandType {
-    type Err = &'static str;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut card_counts: Vec<_> = s.chars()
-                                        .unique()
-                                        .map(|u| s.chars()
-                                                        .filter(|c| u==*c)
-                                                        .count())
-                                        .collect();
-        card_counts.sort_by(|a,b| b.cmp(a));
-        let card_counts = card_counts;
-
-        Ok(match card_counts.get(0) {
-            Some(5) => Self::FiveOfAKind,
-            Some(4) => Self::FourOfAKind,
-            Some(3) => {
-                if card_counts.get(1) == Some(&2) {
-                    Self::FullHouse
-                }
-                else {
-                    Self::ThreeOfAKind
-                }
-            },
-            Some(2) => {
-                if card_counts.get(1) == Some(&2) {
-                    Self::TwoPair
-                }
-                else {
-                    Self::OnePair
-                }
-            }
-            _ => Self::HighCard
-        })
+impl fmt::Display for HandType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.group_sizes[..] {
+            [5] => write!(f, "Five of a kind"),
+            [4, ..] => write!(f, "Four of a kind"),
+            [3, 2, ..] => write!(f, "Full house"),
+            [3, ..] => write!(f, "Three of a kind"),
+            [2, 2, ..] => write!(f, "Two pair"),
+            [2, ..] => write!(f, "One pair"),
+            [1, ..] => write!(f, "High card"),
+            _ => write!(f, "{}", self.group_sizes.iter().join("+"))
+        }
     }
-    
 }
 
-#[derive(Debug, Eq, Ord)]
-struct Hand {
-    _cards: String,
-    bet: i64,
-    hand_type: HandType,
-    score: i64
+struct CardGame {
+    // Weakest card first
+    ordering: Vec<char>,
+    wildcards: Vec<char>
 }
 
-impl FromStr for Hand {
-    type Err = &'static str;
+impl CardGame {
+    fn new(ordering: &str, wildcards: &str) -> Self {
+        Self {
+            ordering: ordering.chars().collect(),
+            wildcards: wildcards.chars().collect()
+        }
+    }
+
+    fn value(&self, card: char) -> Option<usize> {
+        self.ordering.iter().position(|o| *o == card)
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let Some((cards, bet)) = s.split_once(" ") else {
-            return Err("Failed to parse hand");
+    // Classify the hand, along with the card any wildcards in it stand in for
+    fn classify(&self, cards: &str) -> (HandType, Option<char>) {
+        let wildcard_count = cards.chars()
+                                  .filter(|c| self.wildcards.contains(c))
+                                  .count();
+
+        // Largest group first, with the stronger card first between groups of the same size
+        let groups: Vec<_> = cards.chars()
+                                  .filter(|c| !self.wildcards.contains(c))
+                                  .counts()
+                                  .into_iter()
+                                  .sorted_by_key(|(c, count)| (std::cmp::Reverse(*count), std::cmp::Reverse(self.value(*c))))
+                                  .collect();
+
+        let mut group_sizes: Vec<_> = groups.iter().map(|(_, count)| *count).collect();
+
+        // Wildcards always do best by joining the largest group
+        let substitute = match group_sizes.first_mut() {
+            Some(largest) => {
+                *largest += wildcard_count;
+                groups[0].0
+            }
+            None => {
+                group_sizes.push(wildcard_count);
+                *self.ordering.iter().rev().find(|c| !self.wildcards.contains(c)).unwrap_or(&self.ordering[0])
+            }
         };
 
-        let card_vals = HashMap::from([
-            ('A',14),
-            ('K',13),
-            ('Q',12),
-            ('J',11),
-            ('T',10),
-            ('9',9),
-            ('8',8),
-            ('7',7),
-            ('6',6),
-            ('5',5),
-            ('4',4),
-            ('3',3),
-            ('2',2),
-            ('1',1),
-            ('0',0)
-        ]);
-
-        let mut score = 0;
-        let card_count = cards.chars().count();
-        for (i, c) in cards.chars().enumerate() {
-            let power: u32 = (card_count - i) as u32;
-            let index_val = card_vals.iter().count().pow(power) as i64;
-            let card_val = *card_vals.get(&c).unwrap() as i64;
-            score += index_val * card_val;
-        }
+        let substitute = if wildcard_count > 0 { Some(substitute) } else { None };
 
-        Ok(Self {
-            _cards: cards.to_string(),
-            bet: bet.parse().unwrap(),
-            hand_type: HandType::from_str(cards).unwrap(),
-            score, 
-        })
+        (HandType { group_sizes }, substitute)
     }
-}
 
-impl PartialEq for Hand {
-    fn eq(&self, other: &Self) -> bool {
-        self.hand_type == other.hand_type && self.bet == other.bet
+    fn parse_hand(&self, s: &str) -> Result<Hand, String> {
+        let Some((cards, bet)) = s.split_once(' ') else {
+            return Err(format!("Failed to parse hand: {}", s));
+        };
+
+        let card_values = cards.chars()
+            .map(|c| self.value(c).ok_or(format!("Unknown card {} in {}", c, s)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let Ok(bet) = bet.parse() else {
+            return Err(format!("Failed to parse bet: {}", s));
+        };
+
+        let (hand_type, wildcards_as) = self.classify(cards);
+
+        Ok(Hand {
+            hand_type,
+            card_values,
+            cards: cards.to_string(),
+            bet,
+            wildcards_as
+        })
     }
-}
 
-impl PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if self.hand_type != other.hand_type {
-            self.hand_type.partial_cmp(&other.hand_type)
-        }
-        else { 
-            self.score.partial_cmp(&other.score)
-        }
+    fn rank(&self, input: &str) -> Ranking {
+        let mut hands: Vec<_> = input.lines()
+                                     .map(|l| self.parse_hand(l).unwrap())
+                                     .collect();
+
+        hands.sort();
+
+        Ranking { hands }
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
-enum HandTypeWithJoker {
-    FiveOfAKind = 6,
-    FourOfAKind = 5,
-    FullHouse = 4,
-    ThreeOfAKind = 3,
-    TwoPair = 2,
-    OnePair = 1,
-    HighCard = 0
+// Hands order by type, then card by card from the left
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Hand {
+    hand_type: HandType,
+    card_values: Vec<usize>,
+    cards: String,
+    bet: i64,
+    wildcards_as: Option<char>
 }
 
-impl FromStr for HandTypeWithJoker {
-    type Err = &'static str;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let joker_count = s.chars()
-                                    .filter(|c| *c=='J')
-                                    .count();
-
-        let mut card_counts: Vec<_> = s.chars()
-                                        .unique()
-                                        .filter(|u| *u != 'J')
-                                        .map(|u| s.chars()
-                                                        .filter(|c| u==*c)
-                                                        .count())
-                                        .collect();
+// Hands from weakest to strongest
+struct Ranking {
+    hands: Vec<Hand>
+}
 
-        card_counts.sort_by(|a,b| b.cmp(a));
-        let card_counts = card_counts;
+impl Ranking {
+    fn total_winnings(&self) -> i64 {
+        self.hands.iter().enumerate().map(|(i, h)| (i+1) as i64 * h.bet).sum()
+    }
+}
 
-        let card_1_count = *card_counts.get(0).unwrap_or(&0);
-        let card_2_count = *card_counts.get(1).unwrap_or(&0);
+// One line per hand, with how it beat the hand ranked just below it
+impl fmt::Display for Ranking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, hand) in self.hands.iter().enumerate() {
+            write!(f, "{:>5} {} bet {:>4} {}", i + 1, hand.cards, hand.bet, hand.hand_type)?;
 
-        if card_1_count == 5 - joker_count {
-            return Ok(Self::FiveOfAKind);
-        }
+            if let Some(card) = hand.wildcards_as {
+                write!(f, " (wildcards as {})", card)?;
+            }
 
-        if card_1_count == 4 - joker_count {
-            return Ok(Self::FourOfAKind);
-        }
+            let Some(below) = i.checked_sub(1).map(|j| &self.hands[j]) else {
+                writeln!(f)?;
+                continue;
+            };
 
-        if card_1_count == 3 - joker_count {
-            if card_2_count == 2 {
-                return Ok(Self::FullHouse);
+            if hand.hand_type != below.hand_type {
+                writeln!(f, ", beats {} on type", below.cards)?;
+                continue;
             }
-            else {
-                return Ok(Self::ThreeOfAKind);
-            }
-        }
 
-        if card_1_count == 2 - joker_count {
-            if card_2_count == 2 {
-                return Ok(Self::TwoPair);
-            }
-            else {
-                return Ok(Self::OnePair);
+            match (0..hand.card_values.len()).find(|&p| hand.card_values.get(p) != below.card_values.get(p)) {
+                Some(p) => writeln!(f, ", beats {} at card {} ({} > {})", below.cards, p + 1,
+                    hand.cards.chars().nth(p).unwrap(), below.cards.chars().nth(p).map_or('-', |c| c))?,
+                None => writeln!(f, ", ties with {}", below.cards)?
             }
         }
 
-        return Ok(Self::HighCard);
+        Ok(())
     }
-    
 }
 
-#[derive(Debug, Eq, Ord)]
-struct HandWithJoker {
-    _cards: String,
-    bet: i64,
-    hand_type: HandTypeWithJoker,
-    score: i64
+pub fn part1() -> i64 {
+    CardGame::new("23456789TJQKA", "").rank(INPUT).total_winnings()
 }
 
-impl FromStr for HandWithJoker {
-    type Err = &'static str;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let Some((cards, bet)) = s.split_once(" ") else {
-            return Err("Failed to parse hand");
-        };
+pub fn part2() -> i64 {
+    CardGame::new("J23456789TQKA", "J").rank(INPUT).total_winnings()
+}
 
-        let card_vals = HashMap::from([
-            ('A',14),
-            ('K',13),
-            ('Q',12),
-            ('T',11),
-            ('9',10),
-            ('8',9),
-            ('7',8),
-            ('6',7),
-            ('5',6),
-            ('4',5),
-            ('3',4),
-            ('2',3),
-            ('1',2),
-            ('0',1),
-            ('J',0)
-        ]);
-
-        let mut score = 0;
-        let card_count = cards.chars().count();
-        for (i, c) in cards.chars().enumerate() {
-            let power: u32 = (card_count - i) as u32;
-            let index_val = card_vals.iter().count().pow(power) as i64;
-            let card_val = *card_vals.get(&c).unwrap() as i64;
-            score += index_val * card_val;
-        }
+// Every hand in rank order for both parts, and why it beat the one below it
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    let out = format!("Part 1:\n{}\nPart 2:\n{}",
+        CardGame::new("23456789TJQKA", "").rank(INPUT),
+        CardGame::new("J23456789TQKA", "J").rank(INPUT));
 
-        Ok(Self {
-            _cards: cards.to_string(),
-            bet: bet.parse().unwrap(),
-            hand_type: HandTypeWithJoker::from_str(cards).unwrap(),
-            score, 
-        })
-    }
+    super::print_or_write(&out, path)
 }
 
-impl PartialEq for HandWithJoker {
-    fn eq(&self, other: &Self) -> bool {
-        self.hand_type == other.hand_type && self.bet == other.bet
-    }
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl PartialOrd for HandWithJoker {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if self.hand_type != other.hand_type {
-            self.hand_type.partial_cmp(&other.hand_type)
-        }
-        else { 
-            self.score.partial_cmp(&other.score)
-        }
+    fn group_sizes(game: &CardGame, cards: &str) -> (Vec<usize>, Option<char>) {
+        let (hand_type, substitute) = game.classify(cards);
+
+        (hand_type.group_sizes, substitute)
     }
-}
 
-pub fn part1() -> i64 {
-    let mut hands:Vec<_> = INPUT.lines()
-                        .map(|l| Hand::from_str(l).unwrap())
-                        .collect();
+    #[test]
+    fn two_wildcards() {
+        let game = CardGame::new("JQ23456789TKA", "JQ");
 
-    hands.sort();
+        assert_eq!(group_sizes(&game, "JQ223"), (vec![4, 1], Some('2')));
+        assert_eq!(group_sizes(&game, "JQJQJ"), (vec![5], Some('A')));
+        assert_eq!(group_sizes(&game, "23456"), (vec![1, 1, 1, 1, 1], None));
 
-    hands.iter().enumerate().map(|(i, h)| (i+1) as i64 * h.bet).sum()
-}
+        // Wildcards are the weakest cards when breaking ties, whichever card they stand in for
+        let ranking = game.rank("QQQ22 1\nJQ222 2\nKQ22J 3\n22222 4");
+        let cards: Vec<_> = ranking.hands.iter().map(|h| h.cards.as_str()).collect();
 
-pub fn part2() -> i64 {
-    let mut hands:Vec<_> = INPUT.lines()
-                        .map(|l| HandWithJoker::from_str(l).unwrap())
-                        .collect();
+        assert_eq!(cards, ["KQ22J", "JQ222", "QQQ22", "22222"]);
+        assert_eq!(ranking.total_winnings(), 3 + 2 * 2 + 3 + 4 * 4);
+    }
 
-    hands.sort();
+    #[test]
+    fn six_card_hands() {
+        let game = CardGame::new("23456789TJQKA", "");
 
-    hands.iter().enumerate().map(|(i, h)| (i+1) as i64 * h.bet).sum()
-}
\ No newline at end of file
+        assert_eq!(group_sizes(&game, "222222").0, [6]);
+        assert_eq!(group_sizes(&game, "2222KK").0, [4, 2]);
+        assert_eq!(group_sizes(&game, "222KKK").0, [3, 3]);
+        assert_eq!(group_sizes(&game, "AA2233").0, [2, 2, 2]);
+
+        let ranking = game.rank("AAAKKQ 1\n2222KK 2\nAA2233 3\n222KKK 4\n222222 5\nAAAAKQ 6");
+        let cards: Vec<_> = ranking.hands.iter().map(|h| h.cards.as_str()).collect();
+
+        assert_eq!(cards, ["AA2233", "AAAKKQ", "222KKK", "AAAAKQ", "2222KK", "222222"]);
+        assert_eq!(ranking.hands[0].hand_type.to_string(), "Two pair");
+        assert_eq!(ranking.hands[5].hand_type.to_string(), "6");
+    }
+}
@@ -2,145 +2,258 @@ use std::{collections::HashMap, str::FromStr};
 
 const INPUT : &str = include_str!("input/day08.txt");
 
-fn encode_str_to_int(s: &str) -> i64 {
-    let mut encoding = 0;
+// Gives each distinct node label a dense id, in the order they are first seen
+#[derive(Default, Debug)]
+struct Interner {
+    ids: HashMap<String, usize>,
+    names: Vec<String>
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> usize {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
 
-    for (i, c) in s.chars().enumerate() {
-        let val = (c as u8 - 'A' as u8) as i64;
+        let id = self.names.len();
+        self.ids.insert(name.to_string(), id);
+        self.names.push(name.to_string());
+        id
+    }
+
+    fn get(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
 
-        let index_pow = 26_i64.pow(i as u32);
+    fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
 
-        encoding += val * index_pow;
+    fn len(&self) -> usize {
+        self.names.len()
     }
+}
 
-    encoding
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    left: usize,
+    right: usize
 }
 
-fn encode_str_to_int_2(s: &str) -> i64 {
-    let mut encoding = 0;
+// Split "AAA = (BBB, CCC)" into its three labels
+fn parse_node(s: &str) -> Result<(&str, &str, &str), &'static str> {
+    let Some((name, paths)) = s.split_once(" = ") else {
+        return Err("Could not split node");
+    };
+
+    let Some(paths) = paths.trim().strip_prefix('(').and_then(|p| p.strip_suffix(')')) else {
+        return Err("Could not find brackets around paths");
+    };
 
-    for (i, c) in s.chars().rev().enumerate() {
-        let val = if c.is_numeric() {
-            c as u8 - '0' as u8 + 26
+    let Some((left_node, right_node)) = paths.split_once(", ") else {
+        return Err("Could not split paths");
+    };
+
+    Ok((name.trim(), left_node, right_node))
+}
+
+// Where a ghost ends up when following the instructions forever.
+// A state is a node and a position in the instructions, so the walk must eventually repeat.
+#[derive(Debug)]
+struct GhostCycle {
+    // Steps taken before the first state that repeats
+    pre_period: i64,
+    cycle_len: i64,
+    // Every step in [0, pre_period + cycle_len) that lands on an end node
+    end_hits: Vec<i64>
+}
+
+impl GhostCycle {
+    fn at_end(&self, step: i64) -> bool {
+        let step = if step < self.pre_period {
+            step
         }
         else {
-            c as u8 - 'A' as u8 
-        } as i64;
-        
-        let index_pow = 36_i64.pow(i as u32);
+            self.pre_period + (step - self.pre_period) % self.cycle_len
+        };
+
+        self.end_hits.binary_search(&step).is_ok()
+    }
 
-        encoding += val * index_pow;
+    fn cycle_residues(&self) -> Vec<i64> {
+        self.end_hits.iter()
+                     .filter(|&&h| h >= self.pre_period)
+                     .map(|h| h % self.cycle_len)
+                     .collect()
     }
 
-    encoding
+    // The shape the LCM shortcut relies on: no end nodes before the cycle,
+    // and one end node in the cycle, hit exactly every cycle_len steps
+    fn is_simple(&self) -> bool {
+        self.end_hits.iter().all(|&h| h >= self.pre_period) && self.cycle_residues() == [0]
+    }
 }
 
-#[derive(Clone, Debug)]
-struct Node {
-    name: i64,
-    left: i64,
-    right: i64
+struct Network {
+    instructions: Vec<char>,
+    names: Interner,
+    // Indexed by node id
+    nodes: Vec<Node>
 }
 
-impl FromStr for Node {
+impl Network {
+    fn step(&self, node: usize, instruction_i: usize) -> usize {
+        let current_node = self.nodes[node];
+
+        if self.instructions[instruction_i] == 'L' {
+            current_node.left
+        }
+        else {
+            current_node.right
+        }
+    }
+
+    fn find_cycle(&self, start: usize, is_end: impl Fn(&str) -> bool) -> GhostCycle {
+        let mut seen = HashMap::new();
+        let mut end_hits = Vec::new();
+
+        let mut node = start;
+        let mut steps = 0;
+
+        loop {
+            let instruction_i = steps as usize % self.instructions.len();
+
+            if let Some(&first_seen) = seen.get(&(node, instruction_i)) {
+                return GhostCycle { 
+                    pre_period: first_seen, 
+                    cycle_len: steps - first_seen, 
+                    end_hits 
+                };
+            }
+
+            seen.insert((node, instruction_i), steps);
+
+            if is_end(self.names.name(node)) {
+                end_hits.push(steps);
+            }
+
+            node = self.step(node, instruction_i);
+            steps += 1;
+        }
+    }
+}
+
+impl FromStr for Network {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let Some((name, paths)) = s.split_once(" = ") else {
-            return Err("Could not split node");
+        let s = s.replace("\r\n", "\n");
+
+        let Some((instruction_str, nodes_str)) = s.split_once("\n\n") else {
+            return Err("Could not split instructions and nodes");
         };
 
-        let Some((left_node, right_node)) = paths.split_once(", ") else {
-            return Err("Could not split paths");
+        let instructions: Vec<_> = instruction_str.trim().chars().collect();
+
+        if instructions.is_empty() || instructions.iter().any(|c| *c != 'L' && *c != 'R') {
+            return Err("Invalid instructions");
+        }
+
+        let mut names = Interner::default();
+        let mut nodes = Vec::new();
+
+        for line in nodes_str.lines() {
+            let (name, left, right) = parse_node(line)?;
+
+            let id = names.intern(name);
+            let node = Node { left: names.intern(left), right: names.intern(right) };
+
+            nodes.resize(names.len(), None);
+
+            if nodes[id].replace(node).is_some() {
+                return Err("Node defined more than once");
+            }
+        }
+
+        let Some(nodes) = nodes.into_iter().collect::<Option<Vec<_>>>() else {
+            return Err("Node referenced but never defined");
         };
 
-        let mut left_node = left_node.chars();
-        left_node.next();
-        let left_node = left_node.as_str();
+        Ok(Self { instructions, names, nodes })
+    }
+}
 
-        let mut right_node = right_node.chars();
-        right_node.next_back();
-        let right_node = right_node.as_str();
+// Solve t = a (mod m) and t = b (mod n) together, if possible
+fn crt(a: i128, m: i128, b: i128, n: i128) -> Option<(i128, i128)> {
+    let (g, p, _) = extended_gcd(m, n);
 
-        return Ok(Self { 
-            name: encode_str_to_int(name), 
-            left: encode_str_to_int(left_node), 
-            right: encode_str_to_int(right_node) 
-        });
+    if (b - a) % g != 0 {
+        return None;
     }
+
+    let lcm = m / g * n;
+    let t = (a + (b - a) / g % (n / g) * p % (n / g) * m).rem_euclid(lcm);
+
+    Some((t, lcm))
 }
 
-#[derive(Clone, Debug)]
-struct Node2 {
-    name: i64,
-    left: i64,
-    right: i64
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    }
+    else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - a / b * y)
+    }
 }
 
-impl FromStr for Node2 {
-    type Err = &'static str;
+// The first step, after starting, at which every ghost is on an end node
+fn first_simultaneous_end(ghosts: &[GhostCycle]) -> Option<i64> {
+    // Before every ghost is in its cycle, just check each step
+    let all_cycling = ghosts.iter().map(|g| g.pre_period).max().unwrap_or(0).max(1);
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let Some((name, paths)) = s.split_once(" = ") else {
-            return Err("Could not split node");
-        };
+    if ghosts.iter().all(|g| g.is_simple()) {
+        let step = ghosts.iter().fold(1, |acc, g| lcm(acc, g.cycle_len));
 
-        let Some((left_node, right_node)) = paths.split_once(", ") else {
-            return Err("Could not split paths");
-        };
+        // Too early and some ghost is still on its way into the cycle
+        if step >= all_cycling {
+            return Some(step);
+        }
+    }
 
-        let mut left_node = left_node.chars();
-        left_node.next();
-        let left_node = left_node.as_str();
+    if let Some(step) = (1..all_cycling).find(|&s| ghosts.iter().all(|g| g.at_end(s))) {
+        return Some(step);
+    }
 
-        let mut right_node = right_node.chars();
-        right_node.next_back();
-        let right_node = right_node.as_str();
+    // After that, each ghost is at an end on any step matching one of its residues
+    let mut candidates = vec![(0_i128, 1_i128)];
 
-        return Ok(Self { 
-            name: encode_str_to_int_2(name), 
-            left: encode_str_to_int_2(left_node), 
-            right: encode_str_to_int_2(right_node) 
-        });
+    for ghost in ghosts {
+        candidates = candidates.iter()
+            .flat_map(|&(a, m)| ghost.cycle_residues().into_iter()
+                .filter_map(move |r| crt(a, m, r as i128, ghost.cycle_len as i128)))
+            .collect();
     }
+
+    candidates.into_iter()
+              .map(|(a, m)| {
+                  let min_step = all_cycling as i128;
+                  if a >= min_step { a } else { a + (min_step - a + m - 1) / m * m }
+              })
+              .min()
+              .map(|step| step as i64)
 }
 
 pub fn part1() -> i64 {
-    let Some((instruction_str, nodes_str)) = INPUT.split_once("\r\n\r\n") else {
+    let network = Network::from_str(INPUT).unwrap();
+
+    let Some(start) = network.names.get("AAA") else {
         return -1;
     };
 
-    let nodes : HashMap<i64, Node> = nodes_str.lines()
-                                            .map(|n| {
-                                                let node = Node::from_str(n).unwrap();
-                                                return (node.name, node);
-                                            })
-                                            .collect();
-
-    let mut complete = false;
-    let mut current = encode_str_to_int("AAA");
-    let encoded_z = encode_str_to_int("ZZZ");
-    let mut steps = 0;
-    while !complete {
-        for instruction in instruction_str.chars() {
-            let current_node = nodes.get(&current).unwrap();
-
-            if instruction == 'L' {
-                current = current_node.left;
-                steps += 1;
-            }
-            else if instruction == 'R' {
-                current = current_node.right;
-                steps += 1;
-            }
+    let ghost = network.find_cycle(start, |name| name == "ZZZ");
 
-            if current == encoded_z {
-                complete = true;
-            }
-        }
-    }
-
-    steps
+    first_simultaneous_end(&[ghost]).unwrap_or(-1)
 }
  
 fn lcm(first: i64, second: i64) -> i64 {
@@ -151,9 +264,7 @@ fn gcd(first: i64, second: i64) -> i64 {
     let mut max = first;
     let mut min = second;
     if min > max {
-        let val = max;
-        max = min;
-        min = val;
+        std::mem::swap(&mut max, &mut min);
     }
 
     loop {
@@ -168,52 +279,35 @@ fn gcd(first: i64, second: i64) -> i64 {
 }
 
 pub fn part2() -> i64 {
-    let Some((instruction_str, nodes_str)) = INPUT.split_once("\r\n\r\n") else {
-        return -1;
-    };
+    let network = Network::from_str(INPUT).unwrap();
 
-    let nodes : HashMap<i64, Node2> = nodes_str.lines()
-                                            .map(|n| {
-                                                let node = Node2::from_str(n).unwrap();
-                                                return (node.name, node);
-                                            })
-                                            .collect();
-                                            
-    let current : Vec<_> = nodes.iter()
-                                .filter_map(|n| {
-                                    if n.0 % 36 == 0 {
-                                        return Some(*n.0);
-                                    }
-                                    else {
-                                        return None;
-                                    }
-                                })
+    let ghosts: Vec<_> = (0..network.nodes.len())
+                                .filter(|&n| network.names.name(n).ends_with('A'))
+                                .map(|start| network.find_cycle(start, |name| name.ends_with('Z')))
                                 .collect();
 
-    let cycle_counts : Vec<_> = current.iter().map(|c| {
-        let mut steps = 0;
-        let mut current = *c;
-        loop {
-            for instruction in instruction_str.chars() {
-                let current_node = nodes.get(&current).unwrap();
-                if instruction == 'L' {
-                    current = current_node.left;
-                }
-                else if instruction == 'R' {
-                    current = current_node.right;
-                }
-        
-                steps += 1;
-        
-                if current % 36 == ('Z' as u8 - 'A' as u8) as i64 {
-                    return steps;
-                }
-            }
-        }
-    })
-    .collect();
+    first_simultaneous_end(&ghosts).unwrap_or(-1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn end_reached_before_the_lcm() {
+        let network = Network::from_str("L\n\nAAA = (BBB, BBB)\nBBB = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)\n").unwrap();
+        let ghost = network.find_cycle(0, |name| name == "ZZZ");
 
-    cycle_counts.iter().fold(1, |acc, c| {
-        lcm(acc, *c)
-    })
-}
\ No newline at end of file
+        assert_eq!((ghost.pre_period, ghost.cycle_len, &ghost.end_hits[..]), (2, 1, &[2][..]));
+        assert_eq!(first_simultaneous_end(&[ghost]), Some(2));
+    }
+
+    #[test]
+    fn end_hit_before_the_cycle() {
+        // Passes ZZZ once on the way into a loop that only ends on YYZ every other step
+        let network = Network::from_str("L\n\nAAA = (ZZZ, ZZZ)\nZZZ = (BBB, BBB)\nBBB = (YYZ, YYZ)\nYYZ = (BBB, BBB)\n").unwrap();
+        let ghost = network.find_cycle(0, |name| name.ends_with('Z'));
+
+        assert_eq!(first_simultaneous_end(&[ghost]), Some(1));
+    }
+}
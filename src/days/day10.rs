@@ -1,5 +1,5 @@
 use core::panic;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 const INPUT: &str = include_str!("input/day10.txt");
 
@@ -20,27 +20,37 @@ impl NodeBuilder {
 
     fn build(&self, connections: (usize, usize)) -> Node {
         Node {
-            connections: connections
+            connections
         }
     }
 
     fn coords_to_index(&self, x: usize, y: usize) -> usize {
-        x * self.bounds.0 + y
+        y * self.bounds.0 + x
     }
 
     fn index_to_coords(&self, index: usize) -> (usize, usize) {
         (index % self.bounds.0, index / self.bounds.0)
     }
-}
 
-struct Traversal {
-    current: usize,
-    last: usize,
+    // The index of the tile one step in the given compass direction, if it is on the grid
+    fn neighbour(&self, x: usize, y: usize, direction: char) -> Option<usize> {
+        let (x, y) = match direction {
+            'N' => (Some(x), y.checked_sub(1)),
+            'S' => (Some(x), Some(y + 1)),
+            'E' => (Some(x + 1), Some(y)),
+            _ => (x.checked_sub(1), Some(y))
+        };
+
+        match (x, y) {
+            (Some(x), Some(y)) if x < self.bounds.0 && y < self.bounds.1 => Some(self.coords_to_index(x, y)),
+            _ => None
+        }
+    }
 }
 
-fn get_grid(s: &str) -> (HashMap<usize, Node>, usize) {
+fn get_grid(s: &str) -> Result<(HashMap<usize, Node>, usize), String> {
     let grid_height = s.lines().count();
-    let grid_width = s.lines().next().unwrap().chars().count();
+    let grid_width = s.lines().next().map_or(0, |l| l.chars().count());
 
     let node_builder = NodeBuilder::new(grid_width, grid_height);
     let mut grid = HashMap::new();
@@ -49,169 +59,257 @@ fn get_grid(s: &str) -> (HashMap<usize, Node>, usize) {
 
     for (y, line) in s.lines().enumerate() {
         for (x, c) in line.chars().enumerate() {
-            if c != '.' {
-                let connections = match c {
-                    '|' => {
-                        if y == 0 || y == grid_height - 1 {
-                            continue;
-                        }
-
-                        (
-                            node_builder.coords_to_index(x, y - 1),
-                            node_builder.coords_to_index(x, y + 1)
-                        )
-                    },
-                    '-' => {
-                        if x == 0 || x == grid_width - 1 {
-                            continue;
-                        }
-
-                        (
-                            node_builder.coords_to_index(x - 1, y),
-                            node_builder.coords_to_index(x + 1, y)
-                        )
-                    },
-                    'L' => {
-                        if y == 0 || x == grid_width - 1 {
-                            continue;
-                        }
-
-                        (
-                            node_builder.coords_to_index(x, y - 1),
-                            node_builder.coords_to_index(x + 1, y)
-                        )
-                    },
-                    '7' => {
-                        if x == 0 || y == grid_height - 1 {
-                            continue;
-                        }
-
-                        (
-                            node_builder.coords_to_index(x, y + 1),
-                            node_builder.coords_to_index(x - 1, y)
-                        )
-                    },
-                    'J' => {
-                        if x == 0 || y == 0 {
-                            continue;
-                        }
-
-                        (
-                            node_builder.coords_to_index(x, y - 1),
-                            node_builder.coords_to_index(x - 1, y)
-                        )
-                    },
-                    'F' => {
-                        if x == grid_width - 1 || y == grid_height - 1 {
-                            continue;
-                        }
-
-                        (
-                            node_builder.coords_to_index(x, y + 1),
-                            node_builder.coords_to_index(x + 1, y)
-                        )
-                    },
-                    'S' => {
-                        start = Some(node_builder.coords_to_index(x, y));
-                        continue;
+            let directions = match c {
+                '|' => ('N', 'S'),
+                '-' => ('E', 'W'),
+                'L' => ('N', 'E'),
+                'J' => ('N', 'W'),
+                '7' => ('S', 'W'),
+                'F' => ('S', 'E'),
+                '.' => continue,
+                'S' => {
+                    if start.replace(node_builder.coords_to_index(x, y)).is_some() {
+                        return Err("More than one start found".to_string());
                     }
-                    _ => panic!("Invalid char")
-                };
+                    continue;
+                }
+                _ => return Err(format!("Invalid char '{}' at ({}, {})", c, x, y))
+            };
+
+            // A pipe leading off the edge of the grid can never be part of a loop
+            let (Some(first), Some(second)) = (
+                node_builder.neighbour(x, y, directions.0), 
+                node_builder.neighbour(x, y, directions.1)
+            ) else {
+                continue;
+            };
+
+            grid.insert(node_builder.coords_to_index(x, y), node_builder.build((first, second)));
+        }
+    }
+
+    let Some(start) = start else {
+        return Err("No start found".to_string());
+    };
+
+    // A neighbour only counts if it connects back to the start
+    let (start_x, start_y) = node_builder.index_to_coords(start);
+    let candidates: Vec<_> = ['N', 'E', 'S', 'W'].iter()
+        .filter_map(|d| node_builder.neighbour(start_x, start_y, *d))
+        .filter(|n| grid.get(n).is_some_and(|node| node.connections.0 == start || node.connections.1 == start))
+        .collect();
 
-                grid.insert(node_builder.coords_to_index(x, y), node_builder.build(connections));
+    // With more than two candidates, use whichever pair closes the longest loop
+    let mut best: Option<(Node, usize)> = None;
+
+    for (i, first) in candidates.iter().enumerate() {
+        for second in &candidates[i + 1..] {
+            grid.insert(start, node_builder.build((*first, *second)));
+
+            if let Some(path) = walk_loop(&grid, start) {
+                if best.as_ref().is_none_or(|(_, len)| path.len() > *len) {
+                    best = Some((node_builder.build((*first, *second)), path.len()));
+                }
             }
         }
     }
 
-    if start.is_none() {
-        panic!("No start found");
+    let Some((start_node, _)) = best else {
+        return Err(format!("Start at ({}, {}) isn't part of a closed loop", start_x, start_y));
+    };
+
+    grid.insert(start, start_node);
+
+    Ok((grid, start))
+}
+
+// Follow the pipes from start until they lead back to it, or None if they don't form a closed loop
+fn walk_loop(grid: &HashMap<usize, Node>, start: usize) -> Option<Vec<usize>> {
+    let mut path = vec![start];
+    let mut last = start;
+    let mut current = grid.get(&start)?.connections.0;
+
+    while current != start {
+        let node = grid.get(&current)?;
+
+        // Each step has to be connected at both ends
+        let next = if node.connections.0 == last {
+            node.connections.1
+        }
+        else if node.connections.1 == last {
+            node.connections.0
+        }
+        else {
+            return None;
+        };
+
+        if path.len() > grid.len() {
+            return None;
+        }
+
+        path.push(current);
+        last = current;
+        current = next;
     }
 
-    let start = start.unwrap();
-
-    let matches : Vec<_> = grid.iter()
-                            .filter(|(_, node)| 
-                                {
-                                    node.connections.0 == start || 
-                                    node.connections.1 == start
-                                })
-                            .collect();
-    
-    if matches.len() != 2 {
-        panic!("Invalid number of matches");
+    // Arrive back at the start from the other side
+    if grid[&start].connections.1 != last {
+        return None;
     }
 
-    grid.insert(start, node_builder.build((matches[0].0.clone(), matches[1].0.clone())));
+    Some(path)
+}
+
+// The loop through start, beginning and ending at start, and the distance to its furthest point
+fn get_traversal(grid: &HashMap<usize, Node>, start: usize) -> (Vec<usize>, i64) {
+    let mut path = walk_loop(grid, start).unwrap();
 
-    (grid, start)
+    let distance = path.len() as i64 / 2;
+
+    path.push(start);
+
+    (path, distance)
 }
 
-fn get_traversal(grid: HashMap<usize, Node>, start: usize) -> (Vec<usize>, i64) {
-    let mut traversal_1 = Traversal {
-        current: grid[&start].connections.0,
-        last: start
-    };
-    let mut visited_1 = vec![traversal_1.last, traversal_1.current];
+// The pipe character that joins a node to its two connections
+fn pipe_shape(node_builder: &NodeBuilder, index: usize, node: &Node) -> char {
+    let (x, y) = node_builder.index_to_coords(index);
+
+    let points = |connection: usize| {
+        let (cx, cy) = node_builder.index_to_coords(connection);
 
-    let mut traversal_2 = Traversal {
-        current: grid[&start].connections.1,
-        last: start
+        if cy < y { 'N' } else if cy > y { 'S' } else if cx > x { 'E' } else { 'W' }
     };
-    let mut visited_2 = vec![traversal_2.last, traversal_2.current];
 
-    let mut distance = 1;
+    let mut directions = [points(node.connections.0), points(node.connections.1)];
+    directions.sort();
+
+    match directions {
+        ['N', 'S'] => '|',
+        ['E', 'W'] => '-',
+        ['E', 'N'] => 'L',
+        ['N', 'W'] => 'J',
+        ['S', 'W'] => '7',
+        ['E', 'S'] => 'F',
+        _ => panic!("Invalid connections")
+    }
+}
 
-    loop {
-        if traversal_1.current == traversal_2.current {
-            visited_2.pop();
-            break;
-        }
-        else if traversal_1.current == traversal_2.last && traversal_2.current == traversal_1.last  {
-            visited_1.pop();
-            visited_2.pop();
-            break;
-        }
+#[derive(Clone, Copy, PartialEq)]
+enum Tile {
+    Loop(char),
+    // Tiles off the main loop keep their character, which may be a junk pipe
+    Inside(char),
+    Outside(char)
+}
 
-        let temp_traversal_1_last = traversal_1.last;
-        let traversal_1_node = &grid[&traversal_1.current];
-        traversal_1.last = traversal_1.current;
-        if temp_traversal_1_last != traversal_1_node.connections.0 {
-            traversal_1.current = traversal_1_node.connections.0;
-        }
-        else {
-            traversal_1.current = traversal_1_node.connections.1;
-        }
-        visited_1.push(traversal_1.current);
+// Classify every tile as part of the main loop, inside it or outside it
+fn get_tiles(s: &str) -> Result<Vec<Vec<Tile>>, String> {
+    let grid_height = s.lines().count();
+    let grid_width = s.lines().next().unwrap().chars().count();
 
-        let temp_traversal_2_last = traversal_2.last;
-        let traversal_2_node = &grid[&traversal_2.current];
-        traversal_2.last = traversal_2.current;
-        if temp_traversal_2_last != traversal_2_node.connections.0 {
-            traversal_2.current = traversal_2_node.connections.0;
-        }
-        else {
-            traversal_2.current = traversal_2_node.connections.1;
+    let node_builder = NodeBuilder::new(grid_width, grid_height);
+
+    let (grid, start) = get_grid(s)?;
+    let (path, _) = get_traversal(&grid, start);
+    let path: HashSet<_> = path.into_iter().collect();
+
+    Ok(s.lines().enumerate().map(|(y, line)| {
+        // Scan each row, flipping between outside and inside whenever a pipe crosses the row heading north
+        let mut inside = false;
+
+        line.chars().enumerate().map(|(x, c)| {
+            let index = node_builder.coords_to_index(x, y);
+
+            if path.contains(&index) {
+                let shape = pipe_shape(&node_builder, index, &grid[&index]);
+
+                if matches!(shape, '|' | 'L' | 'J') {
+                    inside = !inside;
+                }
+
+                Tile::Loop(shape)
+            }
+            else if inside {
+                Tile::Inside(c)
+            }
+            else {
+                Tile::Outside(c)
+            }
+        })
+        .collect()
+    })
+    .collect())
+}
+
+fn box_char(shape: char) -> char {
+    match shape {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        _ => shape
+    }
+}
+
+// Draw the maze with box-drawing pipes, highlighting the main loop.
+// Empty ground is drawn as I inside the loop and O outside it, and junk pipes are shaded the same way.
+fn render(s: &str, colour: bool) -> Result<String, String> {
+    let mut out = String::new();
+
+    for row in get_tiles(s)? {
+        for tile in row {
+            let (c, ansi) = match tile {
+                Tile::Loop(shape) => (box_char(shape), "1;33"),
+                Tile::Inside('.') => ('I', "1;32"),
+                Tile::Inside(c) => (box_char(c), "32"),
+                Tile::Outside('.') => ('O', "2"),
+                Tile::Outside(c) => (box_char(c), "2")
+            };
+
+            if colour {
+                out.push_str(&format!("\x1b[{}m{}\x1b[0m", ansi, c));
+            }
+            else {
+                out.push(c);
+            }
         }
-        visited_2.push(traversal_2.current);
 
-        distance += 1;
+        out.push('\n');
     }
 
-    visited_2.reverse();
-    visited_1.append(&mut visited_2);
-    (visited_1, distance)
+    Ok(out)
+}
+
+// The maze in colour on the terminal, or as plain text in a file
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    super::print_or_write(&render(INPUT, path.is_none())?, path)
 }
 
 pub fn part1() -> i64 {
-    let (grid, start) = get_grid(INPUT);
+    let (grid, start) = match get_grid(INPUT) {
+        Ok(grid) => grid,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return -1;
+        }
+    };
 
-    get_traversal(grid, start).1
+    get_traversal(&grid, start).1
 }
 
 pub fn part2() -> i64 {
-    let (grid, start) = get_grid(INPUT);
+    let (grid, start) = match get_grid(INPUT) {
+        Ok(grid) => grid,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            return -1;
+        }
+    };
 
-    let (path, distance) = get_traversal(grid, start);
+    let (path, distance) = get_traversal(&grid, start);
 
     let grid_height = INPUT.lines().count();
     let grid_width = INPUT.lines().next().unwrap().chars().count();
@@ -232,5 +330,9 @@ pub fn part2() -> i64 {
 
     let area = (left_lace as i64 - right_lace as i64).abs() / 2;
 
-    area - distance + 1
+    let enclosed = area - distance + 1;
+
+    debug_assert_eq!(enclosed as usize, get_tiles(INPUT).unwrap().iter().flatten().filter(|t| matches!(t, Tile::Inside(_))).count());
+
+    enclosed
 }
\ No newline at end of file
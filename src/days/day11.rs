@@ -4,11 +4,9 @@ fn get_grid(s: &str) -> Vec<Vec<char>> {
     s.lines().map(|l| l.chars().collect()).collect()
 }
 
-fn get_rows_to_expand(grid: &mut Vec<Vec<char>>) -> Vec<usize> {
+fn get_rows_to_expand(grid: &[Vec<char>]) -> Vec<usize> {
     let mut rows_to_expand = Vec::new();
-    for row_i in 0..grid.len() {
-        let row = grid.get(row_i).unwrap();
-
+    for (row_i, row) in grid.iter().enumerate() {
         if row.iter().all(|&c| c == '.') {
             rows_to_expand.push(row_i);
         }
@@ -17,20 +15,10 @@ fn get_rows_to_expand(grid: &mut Vec<Vec<char>>) -> Vec<usize> {
     rows_to_expand
 }
 
-fn get_cols_to_expand(grid: &mut Vec<Vec<char>>) -> Vec<usize> {
+fn get_cols_to_expand(grid: &[Vec<char>]) -> Vec<usize> {
     let mut cols_to_expand = Vec::new();
-    for col_i in 0..grid.get(0).unwrap().len() {
-        let mut column_empty = true;
-
-        for row_i in 0..grid.len() {
-            let c = grid.get(row_i).unwrap().get(col_i).unwrap();
-
-            if *c != '.' {
-                column_empty = false;
-            }
-        }
-
-        if column_empty {
+    for col_i in 0..grid.first().map_or(0, |row| row.len()) {
+        if grid.iter().all(|row| row[col_i] == '.') {
             cols_to_expand.push(col_i);
         }
     }
@@ -38,79 +26,139 @@ fn get_cols_to_expand(grid: &mut Vec<Vec<char>>) -> Vec<usize> {
     cols_to_expand
 }
 
-fn index_to_coords(i: usize, width: usize) -> (usize, usize) {
-    (i % width, i / width)
+// Galaxy positions after every empty row and column has grown to `expansion` times its size
+fn get_expanded_galaxies(grid: &[Vec<char>], expansion: i64) -> Vec<(i64, i64)> {
+    let cols_to_expand = get_cols_to_expand(grid);
+    let rows_to_expand = get_rows_to_expand(grid);
+
+    let expand = |i: usize, to_expand: &[usize]| {
+        let empty_before = to_expand.partition_point(|&e| e < i) as i64;
+
+        i as i64 + empty_before * (expansion - 1)
+    };
+
+    grid.iter()
+        .enumerate()
+        .flat_map(|(y, row)| row.iter()
+                                .enumerate()
+                                .filter(|(_, &c)| c == '#')
+                                .map(move |(x, _)| (x, y)))
+        .map(|(x, y)| (expand(x, &cols_to_expand), expand(y, &rows_to_expand)))
+        .collect()
 }
 
-fn num_is_between(a: usize, b: usize, c: usize) -> bool {
-    let first = if a < b {a} else {b};
-    let second = if a < b {b} else {a};
-    
-    first < c && c < second
+// The galaxies in reading order, after expansion
+struct Universe {
+    galaxies: Vec<(i64, i64)>
 }
 
-pub fn part1() -> i64 {
-    let mut grid = get_grid(INPUT);
+impl Universe {
+    fn new(grid: &[Vec<char>], expansion: i64) -> Self {
+        Self { galaxies: get_expanded_galaxies(grid, expansion) }
+    }
+
+    // Shortest path between two galaxies, numbered from 0
+    fn distance_between(&self, i: usize, j: usize) -> Option<i64> {
+        let (a, b) = (self.galaxies.get(i)?, self.galaxies.get(j)?);
+
+        Some((a.0 - b.0).abs() + (a.1 - b.1).abs())
+    }
 
-    let cols_to_expand = get_cols_to_expand(&mut grid);
-    let rows_to_expand = get_rows_to_expand(&mut grid);
+    fn sum_of_distances(&self) -> i64 {
+        sum_of_pairwise_differences(self.galaxies.iter().map(|g| g.0).collect())
+            + sum_of_pairwise_differences(self.galaxies.iter().map(|g| g.1).collect())
+    }
+}
 
-    let star_locations: Vec<_> = grid.iter()
-                                    .flatten()
-                                    .enumerate()
-                                    .filter_map(|(i, &c)| if c == '#' {Some(i)} else {None})
-                                    .collect();
+// Sum of |a - b| over every pair, using the running total of the smaller values once sorted
+fn sum_of_pairwise_differences(mut values: Vec<i64>) -> i64 {
+    values.sort_unstable();
 
     let mut sum = 0;
-    for i in 0..star_locations.len() {
-        for j in 0..star_locations.len() {
-            if i > j {
-                let (x1, y1) = index_to_coords(star_locations[i], grid.get(0).unwrap().len());
-                let (x2, y2) = index_to_coords(star_locations[j], grid.get(0).unwrap().len());
+    let mut prefix = 0;
+    for (i, v) in values.iter().enumerate() {
+        sum += v * i as i64 - prefix;
+        prefix += v;
+    }
+
+    sum
+}
 
-                let dx = (x2 as i64 - x1 as i64).abs();
-                let dy = (y2 as i64 - y1 as i64).abs();
+pub fn part1() -> i64 {
+    let grid = get_grid(INPUT);
 
-                let dy = dy + rows_to_expand.iter().filter(|&row_i| num_is_between(y1, y2, *row_i)).count() as i64;
-                let dx = dx + cols_to_expand.iter().filter(|&col_i| num_is_between(x1, x2, *col_i)).count() as i64;
+    Universe::new(&grid, 2).sum_of_distances()
+}
 
-                sum += dx + dy;
+pub fn part2() -> i64 {
+    let grid = get_grid(INPUT);
+
+    Universe::new(&grid, 1_000_000).sum_of_distances()
+}
+
+// Each galaxy's expanded position and its nearest neighbour, numbered from 1 as in the puzzle
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    let grid = get_grid(INPUT);
+    let mut out = String::new();
+
+    for expansion in [2, 1_000_000] {
+        let universe = Universe::new(&grid, expansion);
+
+        out.push_str(&format!("Expansion {}:\n", expansion));
+
+        for (i, (x, y)) in universe.galaxies.iter().enumerate() {
+            let nearest = (0..universe.galaxies.len())
+                .filter(|&j| j != i)
+                .filter_map(|j| Some((universe.distance_between(i, j)?, j)))
+                .min();
+
+            match nearest {
+                Some((distance, j)) => out.push_str(&format!("{:>4} ({}, {}) nearest {} at {}\n", i + 1, x, y, j + 1, distance)),
+                None => out.push_str(&format!("{:>4} ({}, {})\n", i + 1, x, y))
             }
         }
     }
 
-    sum
+    super::print_or_write(&out, path)
 }
 
-pub fn part2() -> i64 {
-    let mut grid = get_grid(INPUT);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let cols_to_expand = get_cols_to_expand(&mut grid);
-    let rows_to_expand = get_rows_to_expand(&mut grid);
+    const EXAMPLE: &str = include_str!("input/day11_example.txt");
 
-    let star_locations: Vec<_> = grid.iter()
-                                    .flatten()
-                                    .enumerate()
-                                    .filter_map(|(i, &c)| if c == '#' {Some(i)} else {None})
-                                    .collect();
+    fn brute_force_sum(universe: &Universe) -> i64 {
+        let n = universe.galaxies.len();
 
-    let mut sum = 0;
-    for i in 0..star_locations.len() {
-        for j in 0..star_locations.len() {
-            if i > j {
-                let (x1, y1) = index_to_coords(star_locations[i], grid.get(0).unwrap().len());
-                let (x2, y2) = index_to_coords(star_locations[j], grid.get(0).unwrap().len());
+        (0..n).flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+              .map(|(i, j)| universe.distance_between(i, j).unwrap())
+              .sum()
+    }
 
-                let dx = (x2 as i64 - x1 as i64).abs();
-                let dy = (y2 as i64 - y1 as i64).abs();
+    #[test]
+    fn example() {
+        let grid = get_grid(EXAMPLE);
 
-                let dy = dy + (rows_to_expand.iter().filter(|&row_i| num_is_between(y1, y2, *row_i)).count() * 999_999) as i64;
-                let dx = dx + (cols_to_expand.iter().filter(|&col_i| num_is_between(x1, x2, *col_i)).count() * 999_999) as i64;
+        assert_eq!(Universe::new(&grid, 2).distance_between(4, 8), Some(9));
+        assert_eq!(Universe::new(&grid, 2).distance_between(4, 9), None);
 
-                sum += dx + dy;
-            }
+        for (expansion, sum) in [(2, 374), (10, 1030), (100, 8410)] {
+            let universe = Universe::new(&grid, expansion);
+
+            assert_eq!(universe.sum_of_distances(), sum);
+            assert_eq!(brute_force_sum(&universe), sum);
         }
     }
 
-    sum
-}
\ No newline at end of file
+    #[test]
+    fn sum_matches_every_pair() {
+        let grid = get_grid(INPUT);
+
+        for expansion in [2, 1_000_000] {
+            let universe = Universe::new(&grid, expansion);
+
+            assert_eq!(universe.sum_of_distances(), brute_force_sum(&universe));
+        }
+    }
+}
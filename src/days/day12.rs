@@ -1,7 +1,94 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
 const INPUT: &str = include_str!("input/day12.txt");
 
+// An integer type the arrangement counts can be accumulated in
+trait Count: Clone + fmt::Display {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn is_zero(&self) -> bool;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+}
+
+macro_rules! impl_count {
+    ($($t:ty),*) => {
+        $(impl Count for $t {
+            fn zero() -> Self { 0 }
+            fn one() -> Self { 1 }
+            fn is_zero(&self) -> bool { *self == 0 }
+            fn checked_add(&self, other: &Self) -> Option<Self> { <$t>::checked_add(*self, *other) }
+        })*
+    };
+}
+
+impl_count!(i64, u64, u128);
+
+// Arbitrary precision unsigned integer, only supporting what counting needs.
+// Stored as base 2^64 digits, least significant first.
+#[derive(Clone, Debug, PartialEq)]
+struct BigCount(Vec<u64>);
+
+impl Count for BigCount {
+    fn zero() -> Self {
+        BigCount(Vec::new())
+    }
+
+    fn one() -> Self {
+        BigCount(vec![1])
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|&d| d == 0)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut digits = Vec::new();
+        let mut carry = 0;
+
+        for i in 0..self.0.len().max(other.0.len()) {
+            let sum = *self.0.get(i).unwrap_or(&0) as u128 + *other.0.get(i).unwrap_or(&0) as u128 + carry;
+            digits.push(sum as u64);
+            carry = sum >> 64;
+        }
+
+        if carry > 0 {
+            digits.push(carry as u64);
+        }
+
+        Some(BigCount(digits))
+    }
+}
+
+impl fmt::Display for BigCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Repeatedly divide by 10^19 to peel off decimal chunks
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
+
+        let mut digits = self.0.clone();
+        let mut chunks = Vec::new();
+
+        while digits.iter().any(|&d| d != 0) {
+            let mut remainder = 0;
+
+            for d in digits.iter_mut().rev() {
+                let value = (remainder << 64) | *d as u128;
+                *d = (value / CHUNK) as u64;
+                remainder = value % CHUNK;
+            }
+
+            chunks.push(remainder);
+        }
+
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((most_significant, rest)) => {
+                write!(f, "{}", most_significant)?;
+                rest.iter().rev().try_for_each(|chunk| write!(f, "{:019}", chunk))
+            }
+        }
+    }
+}
+
 struct Puzzle {
     springs: Vec<u8>,
     blocks: Vec<usize>,
@@ -27,11 +114,11 @@ impl FromStr for Puzzle {
 }
 
 impl Puzzle {
-    fn unfold(&self) -> Puzzle {
+    fn unfold(&self, copies: usize) -> Puzzle {
         let mut new_springs = Vec::new();
         let mut new_blocks = Vec::new();
 
-        for _ in 0..5 {
+        for _ in 0..copies {
             new_springs.extend(self.springs.clone());
             new_springs.push(b'?');
             new_blocks.append(&mut self.blocks.clone());
@@ -39,17 +126,19 @@ impl Puzzle {
 
         new_springs.pop();
 
-        return Puzzle {
+        Puzzle {
             springs: new_springs,
             blocks: new_blocks,
-        };
+        }
     }
 
-    fn solve_dp(&self) -> i64 {
+    fn solve_dp<T: Count>(&self) -> Result<T, String> {
+        let overflow = || format!("Arrangement count overflowed {}", std::any::type_name::<T>());
+
         let mut springs = self.springs.clone();
         springs.push(b'.');
 
-        let mut dp_table = vec![vec![0;springs.len()]; self.blocks.len()];
+        let mut dp_table = vec![vec![T::zero(); springs.len()]; self.blocks.len()];
     
         // For each i in springs, max_hash_for_i[i] is the number of # and ? before i.
         let mut sum = 0;
@@ -68,19 +157,19 @@ impl Puzzle {
     
         // Count combinations, handling the first row as a special case.
         let block_0_width = self.blocks[0];
-        let mut combination_count:i64 = 0;
+        let mut combination_count = T::zero();
         let mut valid = true;
     
         for starting_i in 0..wiggle_room {
             // In order to be a broken spring, an interval must only contains `#` or `?`
             // characters and not have a '#' character immediately before or after.
             if springs[starting_i + block_0_width] == b'#' {
-                combination_count = 0;
+                combination_count = T::zero();
             } else if valid && max_hash_for_i[starting_i + block_0_width] - max_hash_for_i[starting_i] == block_0_width {
-                combination_count += 1;
+                combination_count = combination_count.checked_add(&T::one()).ok_or_else(overflow)?;
             }
     
-            dp_table[0][starting_i + block_0_width] = combination_count;
+            dp_table[0][starting_i + block_0_width] = combination_count.clone();
     
             // All following patters are invalid if our window is preceded by a '#'.
             valid &= springs[starting_i] != b'#';
@@ -89,25 +178,198 @@ impl Puzzle {
         let mut next_i = block_0_width + 1;
     
         for (block_i, &block_size) in self.blocks.iter().enumerate().skip(1) {
-            let mut combination_count = 0;
+            let mut combination_count = T::zero();
     
             for i in next_i..next_i + wiggle_room {
                 if springs[i + block_size] == b'#' {
-                    combination_count = 0;
-                } else if dp_table[block_i - 1][i - 1] > 0
+                    combination_count = T::zero();
+                } else if !dp_table[block_i - 1][i - 1].is_zero()
                     && springs[i - 1] != b'#'
                     && max_hash_for_i[i + block_size] - max_hash_for_i[i] == block_size
                 {
-                    combination_count += dp_table[block_i - 1][i - 1];
+                    combination_count = combination_count.checked_add(&dp_table[block_i - 1][i - 1]).ok_or_else(overflow)?;
                 }
     
-                dp_table[block_i][i + block_size] = combination_count;
+                dp_table[block_i][i + block_size] = combination_count.clone();
             }
     
             next_i += block_size + 1;
         }
     
-        *dp_table.last().unwrap().last().unwrap()
+        Ok(dp_table.last().unwrap().last().unwrap().clone())
+    }
+
+    // Whether a block of broken springs can start at i: no '.' inside it and no '#' straight after it
+    fn block_fits(&self, i: usize, block_size: usize) -> bool {
+        let end = i + block_size;
+
+        end <= self.springs.len()
+            && self.springs[i..end].iter().all(|&b| b != b'.')
+            && self.springs.get(end) != Some(&b'#')
+    }
+
+    // completions[i][b] is the number of ways to place blocks b.. in springs i..
+    fn completion_counts<T: Count>(&self) -> Result<Vec<Vec<T>>, String> {
+        let overflow = || format!("Completion count overflowed {}", std::any::type_name::<T>());
+
+        let n = self.springs.len();
+        let block_count = self.blocks.len();
+
+        let mut completions = vec![vec![T::zero(); block_count + 1]; n + 1];
+        completions[n][block_count] = T::one();
+
+        for i in (0..n).rev() {
+            for b in 0..=block_count {
+                let mut count = T::zero();
+
+                if self.springs[i] != b'#' {
+                    count = count.checked_add(&completions[i + 1][b]).ok_or_else(overflow)?;
+                }
+
+                if b < block_count && self.block_fits(i, self.blocks[b]) {
+                    count = count.checked_add(&completions[(i + self.blocks[b] + 1).min(n)][b + 1]).ok_or_else(overflow)?;
+                }
+
+                completions[i][b] = count;
+            }
+        }
+
+        Ok(completions)
+    }
+
+    // Only whether a branch leads anywhere matters here, so counting in a BigCount never fails however long the row
+    fn arrangements(&self) -> Result<Arrangements<'_>, String> {
+        let completions = self.completion_counts::<BigCount>()?;
+
+        let stack = if !completions[0][0].is_zero() {
+            vec![(0, 0, Vec::new())]
+        } else {
+            Vec::new()
+        };
+
+        Ok(Arrangements { puzzle: self, completions, stack })
+    }
+
+    // Pick one valid arrangement, with every arrangement equally likely.
+    // Fails if the row has more arrangements than fit in a u128.
+    fn sample(&self, rng: &mut XorShift) -> Result<Option<Vec<u8>>, String> {
+        let completions = self.completion_counts::<u128>()?;
+        let n = self.springs.len();
+
+        if completions[0][0] == 0 {
+            return Ok(None);
+        }
+
+        let mut arrangement = Vec::new();
+        let (mut i, mut b) = (0, 0);
+
+        while i < n {
+            let as_operational = if self.springs[i] != b'#' { completions[i + 1][b] } else { 0 };
+            let total = completions[i][b];
+
+            if rng.below(total) < as_operational {
+                arrangement.push(b'.');
+                i += 1;
+            } else {
+                let block_end = i + self.blocks[b];
+
+                arrangement.extend(std::iter::repeat_n(b'#', self.blocks[b]));
+                if block_end < n {
+                    arrangement.push(b'.');
+                }
+
+                i = (block_end + 1).min(n);
+                b += 1;
+            }
+        }
+
+        Ok(Some(arrangement))
+    }
+}
+
+// Lazily walks every valid arrangement, only following branches that lead to at least one
+struct Arrangements<'a> {
+    puzzle: &'a Puzzle,
+    completions: Vec<Vec<BigCount>>,
+    stack: Vec<(usize, usize, Vec<u8>)>,
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.puzzle.springs.len();
+
+        while let Some((i, b, arrangement)) = self.stack.pop() {
+            if i == n {
+                return Some(arrangement);
+            }
+
+            if b < self.puzzle.blocks.len() && self.puzzle.block_fits(i, self.puzzle.blocks[b]) {
+                let block_end = i + self.puzzle.blocks[b];
+                let next_i = (block_end + 1).min(n);
+
+                if !self.completions[next_i][b + 1].is_zero() {
+                    let mut with_block = arrangement.clone();
+                    with_block.extend(std::iter::repeat_n(b'#', self.puzzle.blocks[b]));
+                    if block_end < n {
+                        with_block.push(b'.');
+                    }
+
+                    self.stack.push((next_i, b + 1, with_block));
+                }
+            }
+
+            if self.puzzle.springs[i] != b'#' && !self.completions[i + 1][b].is_zero() {
+                let mut with_dot = arrangement;
+                with_dot.push(b'.');
+
+                self.stack.push((i + 1, b, with_dot));
+            }
+        }
+
+        None
+    }
+}
+
+// Small xorshift generator, good enough for sampling arrangements
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // Uniform in [0, bound), rejecting the top values that would make lower results more likely
+    fn below(&mut self, bound: u128) -> u128 {
+        let zone = u128::MAX - u128::MAX % bound;
+
+        loop {
+            let value = (self.next() as u128) << 64 | self.next() as u128;
+
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+// Total the arrangements for every row, reporting rather than wrapping if the total doesn't fit in an i64
+fn total_arrangements(puzzles: &[Puzzle]) -> i64 {
+    let total = puzzles.iter()
+        .map(|p| p.solve_dp::<u128>())
+        .try_fold(0_u128, |acc, count| acc.checked_add(count?).ok_or("Total arrangement count overflowed u128".to_string()))
+        .and_then(|total| i64::try_from(total).map_err(|_| format!("Total arrangement count {} doesn't fit in an i64", total)));
+
+    match total {
+        Ok(total) => total,
+        Err(e) => {
+            println!("ERROR: {}", e);
+            -1
+        }
     }
 }
 
@@ -117,13 +379,13 @@ pub fn part1() -> i64 {
     })
     .collect();
 
-    puzzles.into_iter().map(|p| p.solve_dp()).sum::<i64>()
+    total_arrangements(&puzzles)
 }
 
 pub fn part2() -> i64 {
     let puzzles : Vec<_> = INPUT.lines().filter_map(|l| {
         if let Ok(p) = Puzzle::from_str(l) {
-            Some(p.unfold())
+            Some(p.unfold(5))
         }
         else {
             None
@@ -131,5 +393,186 @@ pub fn part2() -> i64 {
     })
     .collect();
 
-    puzzles.into_iter().map(|p| p.solve_dp()).sum::<i64>()
+    total_arrangements(&puzzles)
+}
+
+// Every arrangement of the rows with only a few, and a handful of random ones for the rest
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    const LISTED: usize = 10;
+    const SAMPLED: usize = 3;
+
+    let mut rng = XorShift(0x2023_1212);
+    let mut out = String::new();
+
+    for line in INPUT.lines() {
+        let puzzle = Puzzle::from_str(line)?;
+        let count = puzzle.solve_dp::<i64>()?;
+
+        out.push_str(&format!("{}: {} arrangements\n", line, count));
+
+        if count as usize <= LISTED {
+            for arrangement in puzzle.arrangements()? {
+                out.push_str(&format!("  {}\n", String::from_utf8_lossy(&arrangement)));
+            }
+        } else {
+            for _ in 0..SAMPLED {
+                if let Some(arrangement) = puzzle.sample(&mut rng)? {
+                    out.push_str(&format!("  {} (sampled)\n", String::from_utf8_lossy(&arrangement)));
+                }
+            }
+        }
+    }
+
+    super::print_or_write(&out, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("input/day12_example.txt");
+
+    fn example_puzzles() -> Vec<Puzzle> {
+        EXAMPLE.lines().map(|l| Puzzle::from_str(l).unwrap()).collect()
+    }
+
+    fn is_valid(puzzle: &Puzzle, arrangement: &[u8]) -> bool {
+        let matches_pattern = arrangement.len() == puzzle.springs.len()
+            && arrangement.iter().zip(&puzzle.springs).all(|(&a, &s)| s == b'?' || a == s);
+
+        let blocks: Vec<_> = arrangement
+            .split(|&b| b == b'.')
+            .filter(|block| !block.is_empty())
+            .map(|block| block.len())
+            .collect();
+
+        matches_pattern && blocks == puzzle.blocks
+    }
+
+    // Try every way of filling in the unknown springs
+    fn brute_force(puzzle: &Puzzle) -> Vec<Vec<u8>> {
+        let unknowns: Vec<_> = (0..puzzle.springs.len()).filter(|&i| puzzle.springs[i] == b'?').collect();
+
+        (0..1_u64 << unknowns.len())
+            .map(|mask| {
+                let mut arrangement = puzzle.springs.clone();
+                for (bit, &i) in unknowns.iter().enumerate() {
+                    arrangement[i] = if mask & (1 << bit) != 0 { b'#' } else { b'.' };
+                }
+                arrangement
+            })
+            .filter(|arrangement| is_valid(puzzle, arrangement))
+            .collect()
+    }
+
+    #[test]
+    fn counts_match_brute_force() {
+        let counts: Vec<_> = example_puzzles().iter().map(|p| p.solve_dp::<i64>().unwrap()).collect();
+        assert_eq!(counts, [1, 4, 1, 1, 4, 10]);
+
+        for puzzle in example_puzzles() {
+            let count = puzzle.solve_dp::<i64>().unwrap();
+
+            assert_eq!(count, puzzle.completion_counts::<i64>().unwrap()[0][0]);
+            assert_eq!(count, brute_force(&puzzle).len() as i64);
+        }
+    }
+
+    #[test]
+    fn arrangements_match_brute_force() {
+        for puzzle in example_puzzles() {
+            let mut arrangements: Vec<_> = puzzle.arrangements().unwrap().collect();
+            arrangements.sort();
+
+            let mut expected = brute_force(&puzzle);
+            expected.sort();
+
+            assert_eq!(arrangements, expected);
+        }
+    }
+
+    #[test]
+    fn samples_are_valid_and_uniform() {
+        let puzzle = Puzzle::from_str("?###???????? 3,2,1").unwrap();
+        let arrangements: Vec<_> = puzzle.arrangements().unwrap().collect();
+
+        let mut rng = XorShift(0x2023_1212);
+        let mut hits = vec![0; arrangements.len()];
+
+        for _ in 0..10_000 {
+            let sampled = puzzle.sample(&mut rng).unwrap().unwrap();
+            assert!(is_valid(&puzzle, &sampled));
+
+            hits[arrangements.iter().position(|a| *a == sampled).unwrap()] += 1;
+        }
+
+        // 1000 expected for each of the 10
+        assert!(hits.iter().all(|&h| (850..1150).contains(&h)), "{:?}", hits);
+    }
+
+    #[test]
+    fn no_arrangements() {
+        let puzzle = Puzzle::from_str("#.# 2").unwrap();
+
+        assert_eq!(puzzle.solve_dp::<i64>().unwrap(), 0);
+        assert_eq!(puzzle.arrangements().unwrap().count(), 0);
+        assert_eq!(puzzle.sample(&mut XorShift(1)), Ok(None));
+    }
+
+    #[test]
+    fn unfolded_counts_agree() {
+        for puzzle in example_puzzles().iter().map(|p| p.unfold(5)) {
+            assert_eq!(puzzle.solve_dp::<u128>().unwrap().to_string(), puzzle.solve_dp::<BigCount>().unwrap().to_string());
+        }
+
+        let counts: Vec<_> = example_puzzles().iter().map(|p| p.unfold(5).solve_dp::<i64>().unwrap()).collect();
+        assert_eq!(counts, [1, 16384, 1, 16, 2500, 506250]);
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        // Too many arrangements for an i64, but a u128 still holds them
+        let puzzle = Puzzle::from_str("?###???????? 3,2,1").unwrap().unfold(30);
+        let expected = "127834039488589391112327575683593750";
+
+        assert!(puzzle.solve_dp::<i64>().is_err());
+        assert!(puzzle.completion_counts::<i64>().is_err());
+        assert_eq!(puzzle.solve_dp::<u128>().unwrap().to_string(), expected);
+        assert_eq!(puzzle.solve_dp::<BigCount>().unwrap().to_string(), expected);
+        assert_eq!(puzzle.completion_counts::<BigCount>().unwrap()[0][0].to_string(), expected);
+        assert!(is_valid(&puzzle, &puzzle.sample(&mut XorShift(1)).unwrap().unwrap()));
+
+        // Too many even for a u128, so sampling fails while enumerating still works
+        let puzzle = Puzzle::from_str("?###???????? 3,2,1").unwrap().unfold(40);
+
+        assert!(puzzle.solve_dp::<u128>().is_err());
+        assert!(puzzle.sample(&mut XorShift(1)).is_err());
+        assert!(puzzle.solve_dp::<BigCount>().is_ok());
+
+        let first = puzzle.arrangements().unwrap().next().unwrap();
+        assert!(is_valid(&puzzle, &first));
+    }
+
+    #[test]
+    fn big_count_carries() {
+        let max = BigCount(vec![u64::MAX]);
+
+        assert_eq!(max.checked_add(&BigCount::one()), Some(BigCount(vec![0, 1])));
+        assert_eq!(BigCount(vec![u64::MAX, u64::MAX]).checked_add(&BigCount::one()), Some(BigCount(vec![0, 0, 1])));
+        assert_eq!(BigCount(vec![1, 2]).checked_add(&BigCount(vec![3])), Some(BigCount(vec![4, 2])));
+        assert_eq!(BigCount::zero().checked_add(&max), Some(max));
+    }
+
+    #[test]
+    fn big_count_display() {
+        assert_eq!(BigCount::zero().to_string(), "0");
+        assert_eq!(BigCount(vec![0, 0]).to_string(), "0");
+        assert_eq!(BigCount(vec![12345]).to_string(), "12345");
+        assert_eq!(BigCount(vec![u64::MAX]).to_string(), u64::MAX.to_string());
+        assert_eq!(BigCount(vec![0, 1]).to_string(), "18446744073709551616");
+        assert_eq!(BigCount(vec![u64::MAX, u64::MAX]).to_string(), u128::MAX.to_string());
+        // 10^19 exactly, so the lower chunk is padded with zeros
+        assert_eq!(BigCount(vec![10_000_000_000_000_000_000]).to_string(), "10000000000000000000");
+        assert_eq!(BigCount(vec![0, 0, 1]).to_string(), "340282366920938463463374607431768211456");
+    }
 }
@@ -1,139 +1,233 @@
+use std::{fmt, str::FromStr};
+
 const INPUT: &str = include_str!("input/day13.txt");
 
-fn transpose<T>(v: Vec<Vec<T>>) -> Vec<Vec<T>> {
-    assert!(!v.is_empty());
-    let len = v[0].len();
-    let mut iters: Vec<_> = v.into_iter().map(|n| n.into_iter()).collect();
-    (0..len)
-        .map(|_| {
-            iters
-                .iter_mut()
-                .map(|n| n.next().unwrap())
-                .collect::<Vec<T>>()
-        })
-        .collect()
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Axis {
+    // A line between two rows
+    Horizontal,
+    // A line between two columns
+    Vertical
 }
 
-fn find_vertical_mirror(input: &Vec<Vec<char>>) -> Option<i64> {
-    // Switch the rows and the columns in the input
-    let transposed_input = transpose(input.clone());
-
-    find_horizontal_mirror(&transposed_input)
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Reflection {
+    axis: Axis,
+    // Number of rows above or columns left of the line
+    position: usize
 }
 
-fn find_vertical_mirror_one_off(input: &Vec<Vec<char>>) -> Option<i64> {
-    // Switch the rows and the columns in the input
-    let transposed_input = transpose(input.clone());
+impl Reflection {
+    fn summary(&self) -> i64 {
+        match self.axis {
+            Axis::Horizontal => 100 * self.position as i64,
+            Axis::Vertical => self.position as i64
+        }
+    }
+}
 
-    find_horizontal_mirror_one_off(&transposed_input)
+// Each row and column stored as a bitmask with a bit set for every '#'
+#[derive(Clone)]
+struct Pattern {
+    rows: Vec<u64>,
+    cols: Vec<u64>
 }
 
-fn find_horizontal_mirror(input: &Vec<Vec<char>>) -> Option<i64> {
-    
-    for i in 1..input.len() {
-        let mut up_rows = (&input[0..i]).to_vec();
-        up_rows.reverse();
-        let down_rows = (&input[i..input.len()]).to_vec();
+impl FromStr for Pattern {
+    type Err = String;
 
-        let min_row_i = up_rows.len().min(down_rows.len());
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let grid: Vec<Vec<char>> = s.lines().map(|l| l.trim().chars().collect()).collect();
 
-        let mut mirrored = true;
+        let width = grid.first().map_or(0, |row| row.len());
+        let height = grid.len();
 
-        for i in 0..min_row_i {
-            if up_rows[i] != down_rows[i] {
-                mirrored = false;
-                break;
-            }
+        if width == 0 || width > 64 || height > 64 {
+            return Err(format!("Pattern must be between 1 and 64 tiles wide and tall, found {}x{}", width, height));
         }
-        if mirrored {
-            return Some(i as i64);
+
+        if grid.iter().any(|row| row.len() != width) {
+            return Err("Pattern rows are different lengths".to_string());
+        }
+
+        let mut rows = vec![0; height];
+        let mut cols = vec![0; width];
+
+        for (y, row) in grid.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                match c {
+                    '#' => {
+                        rows[y] |= 1 << x;
+                        cols[x] |= 1 << y;
+                    },
+                    '.' => {},
+                    _ => return Err(format!("Invalid char '{}' in pattern", c))
+                }
+            }
         }
-    }
 
-    None
+        Ok(Self { rows, cols })
+    }
 }
 
-fn find_horizontal_mirror_one_off(input: &Vec<Vec<char>>) -> Option<i64> {
-    for i in 1..input.len() {
-        let mut up_rows = (&input[0..i]).to_vec();
-        up_rows.reverse();
-        let down_rows = (&input[i..input.len()]).to_vec();
+impl Pattern {
+    // Every reflection line that needs exactly `smudges` tiles flipping to be perfect
+    fn reflections(&self, smudges: u32) -> Vec<Reflection> {
+        let horizontal = find_mirrors(&self.rows, smudges)
+            .into_iter()
+            .map(|position| Reflection { axis: Axis::Horizontal, position });
 
-        let min_row_i = up_rows.len().min(down_rows.len());
+        let vertical = find_mirrors(&self.cols, smudges)
+            .into_iter()
+            .map(|position| Reflection { axis: Axis::Vertical, position });
 
-        let mut flaws = 0;
+        horizontal.chain(vertical).collect()
+    }
 
-        for i in 0..min_row_i {
-            if up_rows[i] != down_rows[i] {
-                for j in 0..up_rows[i].len() {
-                    let up_rows_j = up_rows[i][j];
-                    let down_rows_j = down_rows[i][j];
+    fn is_rock(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & (1 << x) != 0
+    }
 
-                    if up_rows_j != down_rows_j {
-                        flaws+=1;
-                    }
-                }
+    fn flip(&mut self, x: usize, y: usize) {
+        self.rows[y] ^= 1 << x;
+        self.cols[x] ^= 1 << y;
+    }
 
-                if flaws > 1 {
-                    break;
-                }
+    // The (x, y) of each tile that doesn't match its mirror image, taken from the top or left side of the line
+    fn smudges(&self, reflection: &Reflection) -> Vec<(usize, usize)> {
+        let lines = match reflection.axis {
+            Axis::Horizontal => &self.rows,
+            Axis::Vertical => &self.cols
+        };
+
+        let mut smudges = Vec::new();
+
+        for (up, down) in (0..reflection.position).rev().zip(reflection.position..lines.len()) {
+            let differences = lines[up] ^ lines[down];
+
+            for bit in (0..64).filter(|b| differences & (1 << b) != 0) {
+                smudges.push(match reflection.axis {
+                    Axis::Horizontal => (bit, up),
+                    Axis::Vertical => (up, bit)
+                });
             }
         }
 
-        if flaws == 1 {
-            return Some(i as i64);
+        smudges
+    }
+
+    // Draw the pattern with the reflection line between the rows or columns
+    fn render(&self, reflection: &Reflection) -> String {
+        let width = self.cols.len();
+        let mut out = String::new();
+
+        for y in 0..self.rows.len() {
+            if reflection.axis == Axis::Horizontal && y == reflection.position {
+                out.push_str(&"-".repeat(width));
+                out.push('\n');
+            }
+
+            for x in 0..width {
+                if reflection.axis == Axis::Vertical && x == reflection.position {
+                    out.push('|');
+                }
+
+                out.push(if self.is_rock(x, y) { '#' } else { '.' });
+            }
+
+            out.push('\n');
         }
+
+        out
     }
+}
 
-    None
+// A reflection found by fixing smudges, and the pattern once they're fixed
+struct Repair {
+    reflection: Reflection,
+    smudges: Vec<(usize, usize)>,
+    repaired: Pattern
 }
 
-pub fn part1() -> i64 {
-    let patterns: Vec<_> = INPUT.split("\r\n\r\n").collect();
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let smudges: Vec<_> = self.smudges.iter().map(|(x, y)| format!("({}, {})", x, y)).collect();
 
-    let patterns: Vec<_> = patterns
-        .iter()
-        .map(|p| {
-            p.lines()
-                .map(|l| l.chars().collect::<Vec<_>>())
-                .collect::<Vec<_>>()
-        })
-        .collect();
+        writeln!(f, "Smudge at {}, {:?} reflection at {}", smudges.join(", "), self.reflection.axis, self.reflection.position)?;
+        write!(f, "{}", self.repaired.render(&self.reflection))
+    }
+}
 
-    let vertical_mirror_sums: i64 = patterns
-        .iter()
-        .filter_map(|p| find_vertical_mirror(p))
-        .sum();
+fn find_repairs(pattern: &Pattern, smudges: u32) -> Vec<Repair> {
+    pattern.reflections(smudges)
+        .into_iter()
+        .map(|reflection| {
+            let smudges = pattern.smudges(&reflection);
 
-    let horizontal_mirror_sums: i64 = patterns
-        .iter()
-        .filter_map(|p| find_horizontal_mirror(p))
-        .sum();
-    
-    vertical_mirror_sums + (horizontal_mirror_sums * 100) 
+            let mut repaired = pattern.clone();
+            smudges.iter().for_each(|&(x, y)| repaired.flip(x, y));
+
+            debug_assert!(repaired.reflections(0).contains(&reflection));
+
+            Repair { reflection, smudges, repaired }
+        })
+        .collect()
 }
 
-pub fn part2() -> i64 {
-    let patterns: Vec<_> = INPUT.split("\r\n\r\n").collect();
+// Positions between lines where the lines either side mirror each other,
+// with exactly `smudges` mismatched tiles across the whole reflection
+fn find_mirrors(lines: &[u64], smudges: u32) -> Vec<usize> {
+    (1..lines.len())
+        .filter(|&i| {
+            let mut differences = 0;
 
-    let patterns: Vec<_> = patterns
-        .iter()
-        .map(|p| {
-            p.lines()
-                .map(|l| l.chars().collect::<Vec<_>>())
-                .collect::<Vec<_>>()
+            for (up, down) in lines[..i].iter().rev().zip(&lines[i..]) {
+                differences += (up ^ down).count_ones();
+
+                if differences > smudges {
+                    return false;
+                }
+            }
+
+            differences == smudges
         })
-        .collect();
+        .collect()
+}
+
+fn get_patterns(s: &str) -> Vec<Pattern> {
+    s.replace("\r\n", "\n")
+        .split("\n\n")
+        .map(|p| Pattern::from_str(p).unwrap())
+        .collect()
+}
 
-    let vertical_mirror_sums: i64 = patterns
+pub fn part1() -> i64 {
+    get_patterns(INPUT)
         .iter()
-        .filter_map(|p| find_vertical_mirror_one_off(p))
-        .sum();
+        .flat_map(|p| p.reflections(0))
+        .map(|r| r.summary())
+        .sum()
+}
 
-    let horizontal_mirror_sums: i64 = patterns
+pub fn part2() -> i64 {
+    get_patterns(INPUT)
         .iter()
-        .filter_map(|p| find_horizontal_mirror_one_off(p))
-        .sum();
-    
-    vertical_mirror_sums + (horizontal_mirror_sums * 100) 
+        .flat_map(|p| find_repairs(p, 1))
+        .map(|r| r.reflection.summary())
+        .sum()
+}
+
+// Where each pattern's smudge is, and the repaired pattern with its new reflection line
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    let mut out = String::new();
+
+    for (i, pattern) in get_patterns(INPUT).iter().enumerate() {
+        out.push_str(&format!("Pattern {}:\n", i + 1));
+
+        for repair in find_repairs(pattern, 1) {
+            out.push_str(&format!("{}\n", repair));
+        }
+    }
+
+    super::print_or_write(&out, path)
 }
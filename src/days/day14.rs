@@ -1,116 +1,121 @@
-use std::collections::HashMap;
+use std::str::FromStr;
 
-use itertools::Itertools;
+use crate::cycle;
 
 const INPUT: &str = include_str!("input/day14.txt");
 
-fn rotate_vec(vec: &mut Vec<Vec<char>>) {
-    let mut reversed = vec.clone();
-    reversed.iter_mut().for_each(|row| row.reverse());
-
-    let transposed = transpose(reversed);
-
-    *vec = transposed
+#[derive(Clone, Copy)]
+enum Direction {
+    North,
+    West,
+    South,
+    East
 }
 
-fn transpose<T>(v: Vec<Vec<T>>) -> Vec<Vec<T>> {
-    assert!(!v.is_empty());
-    let len = v[0].len();
-    let mut iters: Vec<_> = v.into_iter().map(|n| n.into_iter()).collect();
-    (0..len)
-        .map(|_| {
-            iters
-                .iter_mut()
-                .map(|n| n.next().unwrap())
-                .collect::<Vec<T>>()
-        })
-        .collect()
+// The platform as one row-major byte per tile
+#[derive(Clone)]
+struct Platform {
+    width: usize,
+    height: usize,
+    tiles: Vec<u8>
 }
 
-fn roll_upwards(grid: &mut Vec<Vec<char>>) {
-    grid.iter_mut().for_each(|col| {
-        let mut hash_groups_shifted = Vec::new();
-        let col_str = col.iter().collect::<String>();
+impl FromStr for Platform {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let height = s.lines().count();
+        let width = s.lines().next().map_or(0, |l| l.len());
 
-        for hash_group in col_str.split('#') {
-            let round_count = hash_group.chars().filter(|c| *c == 'O').count();
+        let tiles: Vec<u8> = s.lines().flat_map(|l| l.bytes()).collect();
 
-            hash_groups_shifted.push(hash_group.replace('O', ".").replacen('.', "O", round_count));
+        if tiles.len() != width * height {
+            return Err("Rows are different lengths");
         }
 
-        *col = hash_groups_shifted.join("#").chars().collect_vec();
-    });
-}
+        if tiles.iter().any(|t| !matches!(t, b'O' | b'#' | b'.')) {
+            return Err("Invalid tile");
+        }
 
-fn cycle_rotate_and_roll(grid: &mut Vec<Vec<char>>) {
-    roll_upwards(grid);
-    rotate_vec(grid);
-    roll_upwards(grid);
-    rotate_vec(grid);
-    roll_upwards(grid);
-    rotate_vec(grid);
-    roll_upwards(grid);
-    rotate_vec(grid);
+        Ok(Self { width, height, tiles })
+    }
 }
 
-fn get_grid_from_str(grid_str: &str) -> Vec<Vec<char>> {
-    let lines: Vec<Vec<_>> = grid_str.lines().map(|l| l.chars().collect()).collect();
-    let grid_h = grid_str.lines().count();
-    let grid_w = lines.get(0).unwrap().len();
-
-    let mut grid = vec![vec!['.'; grid_h]; grid_w];
+impl Platform {
+    // Roll every round rock as far as it goes in the given direction
+    fn tilt(&mut self, direction: Direction) {
+        let (width, height) = (self.width, self.height);
+
+        // Walk each lane starting from the edge the rocks roll towards
+        let (lane_count, lane_len) = match direction {
+            Direction::North | Direction::South => (width, height),
+            Direction::West | Direction::East => (height, width)
+        };
+
+        let index = |lane: usize, pos: usize| match direction {
+            Direction::North => pos * width + lane,
+            Direction::South => (height - 1 - pos) * width + lane,
+            Direction::West => lane * width + pos,
+            Direction::East => lane * width + (width - 1 - pos)
+        };
+
+        for lane in 0..lane_count {
+            let mut free = 0;
+
+            for pos in 0..lane_len {
+                match self.tiles[index(lane, pos)] {
+                    b'#' => free = pos + 1,
+                    b'O' => {
+                        if pos != free {
+                            self.tiles[index(lane, free)] = b'O';
+                            self.tiles[index(lane, pos)] = b'.';
+                        }
+                        free += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
 
-    for x_i in 0..grid_w {
-        for y_i in 0..grid_h {
-            grid[x_i][y_i] = lines[y_i][x_i];
+    fn spin_cycle(&mut self) {
+        for direction in [Direction::North, Direction::West, Direction::South, Direction::East] {
+            self.tilt(direction);
         }
     }
 
-    return grid;
-}
+    fn north_load(&self) -> i64 {
+        self.tiles.iter()
+            .enumerate()
+            .filter(|(_, &t)| t == b'O')
+            .map(|(i, _)| (self.height - i / self.width) as i64)
+            .sum()
+    }
 
-fn calculate_load_on_grid(grid: &Vec<Vec<char>>) -> i64 {
-    grid.iter()
-        .map(|col| {
-            let h = col.len();
-            col.iter()
-                .enumerate()
-                .filter_map(|(i, c)| if *c == 'O' {Some((h - i) as i64)} else {None}).sum::<i64>()
-        })
-        .sum()
-}
+    // The round rock positions as a bitset, which is all that changes between spin cycles
+    fn round_rocks(&self) -> Vec<u64> {
+        let mut bits = vec![0; self.tiles.len().div_ceil(64)];
 
-pub fn part1() -> i64 {
-    let mut grid = get_grid_from_str(INPUT);
+        for (i, _) in self.tiles.iter().enumerate().filter(|(_, &t)| t == b'O') {
+            bits[i / 64] |= 1 << (i % 64);
+        }
 
-    roll_upwards(&mut grid);
-    
-    calculate_load_on_grid(&grid)
+        bits
+    }
 }
 
-pub fn part2() -> i64 {    
-    let mut grid = get_grid_from_str(INPUT);
-    
-    let mut cache: HashMap<Vec<Vec<char>>, usize> = HashMap::new();
-
-    let mut cycle_offset = None;
+pub fn part1() -> i64 {
+    let mut platform = Platform::from_str(INPUT).unwrap();
 
-    for i in 0..1_000_000_000 {
-        if let Some(cached_value) = cache.get(&grid) {
-            let cycle_len = i - cached_value;
+    platform.tilt(Direction::North);
 
-            cycle_offset = Some((1_000_000_000 - cached_value) % cycle_len);
-            break;
-        }
+    platform.north_load()
+}
 
-        cache.insert(grid.clone(), i);
+pub fn part2() -> i64 {
+    let platform = Platform::from_str(INPUT).unwrap();
 
-        cycle_rotate_and_roll(&mut grid);
-    }
+    let platform = cycle::state_after(platform, 1_000_000_000, Platform::spin_cycle, Platform::round_rocks);
 
-    for _ in 0..cycle_offset.unwrap() {
-        cycle_rotate_and_roll(&mut grid);
-    }
-    
-    calculate_load_on_grid(&grid)
+    platform.north_load()
 }
@@ -1,21 +1,51 @@
-use std::str::FromStr;
+use std::{collections::HashSet, fmt, str::FromStr};
 
 use itertools::Itertools;
 
 const INPUT: &str = include_str!("input/day15.txt");
 
-fn char_to_ascii(c: char) -> i64 {
-    c as i64
+// Add each character's ASCII code, multiply, then take the remainder
+#[derive(Clone, Copy)]
+struct Hasher {
+    multiplier: i64,
+    modulus: i64,
 }
 
-fn add_to_hash(current_hash: i64, new_val: char) -> i64 {
-    let new_val = char_to_ascii(new_val);
-    let new_hash = (current_hash + new_val) * 17;
-    new_hash % 256
-}
+impl Hasher {
+    // The puzzle's Holiday ASCII String Helper
+    const HASH: Hasher = Hasher {
+        multiplier: 17,
+        modulus: 256,
+    };
+
+    // Every box is allocated up front, so keep the count sensible
+    const MAX_MODULUS: i64 = 1 << 24;
+
+    fn new(multiplier: i64, modulus: i64) -> Result<Self, String> {
+        if !(1..=Self::MAX_MODULUS).contains(&modulus) {
+            return Err(format!("Modulus must be between 1 and {}, found {}", Self::MAX_MODULUS, modulus));
+        }
+
+        if multiplier < 1 {
+            return Err(format!("Multiplier must be positive, found {}", multiplier));
+        }
+
+        // The most that is ever multiplied is the largest remainder plus the largest char
+        if (modulus - 1 + char::MAX as i64).checked_mul(multiplier).is_none() {
+            return Err(format!("Multiplier {} overflows with modulus {}", multiplier, modulus));
+        }
+
+        Ok(Self { multiplier, modulus })
+    }
+
+    fn add_to_hash(&self, current_hash: i64, new_val: char) -> i64 {
+        let new_hash = (current_hash + new_val as i64) * self.multiplier;
+        new_hash % self.modulus
+    }
 
-fn hash_from_string(input: &str) -> i64 {
-    input.chars().fold(0, |acc, c| add_to_hash(acc, c))
+    fn hash(&self, input: &str) -> i64 {
+        input.chars().fold(0, |acc, c| self.add_to_hash(acc, c))
+    }
 }
 
 pub fn part1() -> i64 {
@@ -23,85 +53,289 @@ pub fn part1() -> i64 {
 
     let sum: i64 = input_no_whitespace
         .split(",")
-        .map(|s| hash_from_string(s))
+        .map(|s| Hasher::HASH.hash(s))
         .sum();
 
     sum
 }
 
 enum LensOperation {
-    Add(String, i64, i64), // Label, Focal Length, Box number
-    Remove(String, i64),   // Label, Box number
+    Add(String, i64), // Label, Focal Length
+    Remove(String),   // Label
+}
+
+impl LensOperation {
+    fn label(&self) -> &str {
+        match self {
+            LensOperation::Add(label, _) | LensOperation::Remove(label) => label,
+        }
+    }
 }
 
 impl FromStr for LensOperation {
-    type Err = &'static str;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(label) = s.strip_suffix('-') {
+            Ok(Self::Remove(label.to_string()))
+        } else if let Some((label, focal_len)) = s.split_once('=') {
+            let Ok(focal_len) = focal_len.parse::<i64>() else {
+                return Err(format!("Failed to parse focal length in {}", s));
+            };
 
-        if s.contains('-') {
-            let split = s.split('-').collect_vec();
-            let label = split.get(0).unwrap();
-            let hash = hash_from_string(label);
-
-            Ok(Self::Remove(label.to_string(), hash))
+            Ok(Self::Add(label.to_string(), focal_len))
         } else {
-            let split = s.split('=').collect_vec();
-
-            let label = split.get(0).unwrap();
-            let focal_len = split.get(1).unwrap().parse::<i64>().unwrap();
-            let hash = hash_from_string(label);
+            Err(format!("Failed to find operation in {}", s))
+        }
+    }
+}
 
-            Ok(Self::Add(label.to_string(), focal_len, hash))
+impl fmt::Display for LensOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LensOperation::Add(label, focal_len) => write!(f, "{}={}", label, focal_len),
+            LensOperation::Remove(label) => write!(f, "{}-", label),
         }
     }
 }
 
-fn apply_operation(boxes: &mut Vec<Vec<(String, i64)>>, op: LensOperation) {
-    match op {
-        LensOperation::Add(label, focal_len, box_i) => {
-            let box_ref = &mut boxes[box_i as usize];
+struct LensPower {
+    label: String,
+    box_i: usize,
+    slot: usize,
+    focal_len: i64,
+}
+
+impl LensPower {
+    fn power(&self) -> i64 {
+        (self.box_i + 1) as i64 * (self.slot + 1) as i64 * self.focal_len
+    }
+}
+
+impl fmt::Display for LensPower {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} (box {}) * {} (slot {}) * {} (focal length) = {}",
+            self.label,
+            self.box_i + 1,
+            self.box_i,
+            self.slot + 1,
+            self.slot + 1,
+            self.focal_len,
+            self.power()
+        )
+    }
+}
+
+struct LensBoxes {
+    hasher: Hasher,
+    boxes: Vec<Vec<(String, i64)>>,
+    // The most lenses any one box has held at once
+    peak_chain: usize,
+}
+
+impl LensBoxes {
+    fn new(hasher: Hasher) -> Self {
+        Self {
+            hasher,
+            boxes: vec![vec![]; hasher.modulus as usize],
+            peak_chain: 0,
+        }
+    }
+
+    fn slot(&self, box_i: usize, label: &str) -> Option<usize> {
+        self.boxes[box_i].iter().position(|(l, _)| l == label)
+    }
+
+    // The box and slot a label is currently in
+    fn locate(&self, label: &str) -> Option<(usize, usize)> {
+        let box_i = self.hasher.hash(label) as usize;
+
+        self.slot(box_i, label).map(|slot| (box_i, slot))
+    }
 
-            if let Some((existing_i, _)) = box_ref.iter().find_position(|(l, _)| *l == label) {
-                box_ref[existing_i] = (label, focal_len);
-            } else {
-                box_ref.push((label, focal_len));
+    fn apply(&mut self, op: &LensOperation) {
+        let box_i = self.hasher.hash(op.label()) as usize;
+
+        match op {
+            LensOperation::Add(label, focal_len) => {
+                match self.slot(box_i, label) {
+                    Some(slot) => self.boxes[box_i][slot].1 = *focal_len,
+                    None => self.boxes[box_i].push((label.clone(), *focal_len)),
+                }
+
+                self.peak_chain = self.peak_chain.max(self.boxes[box_i].len());
+            }
+            LensOperation::Remove(label) => {
+                if let Some(slot) = self.slot(box_i, label) {
+                    self.boxes[box_i].remove(slot);
+                }
             }
         }
-        LensOperation::Remove(label, box_i) => {
-            let box_ref = &mut boxes[box_i as usize];
+    }
+
+    // Apply each operation in turn, writing out the boxes after each one if given a trace
+    fn run(operations: &[LensOperation], hasher: Hasher, mut trace: Option<&mut String>) -> Self {
+        let mut boxes = Self::new(hasher);
 
-            if let Some((existing_i, _)) = box_ref.iter().find_position(|(l, _)| *l == label) {
-                box_ref.remove(existing_i);
+        for op in operations {
+            boxes.apply(op);
+
+            if let Some(trace) = trace.as_mut() {
+                trace.push_str(&format!("After \"{}\":\n{}\n", op, boxes));
             }
         }
+
+        boxes
+    }
+
+    fn lens_powers(&self) -> Vec<LensPower> {
+        self.boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(box_i, b)| {
+                b.iter()
+                    .enumerate()
+                    .map(move |(slot, (label, focal_len))| LensPower {
+                        label: label.clone(),
+                        box_i,
+                        slot,
+                        focal_len: *focal_len,
+                    })
+            })
+            .collect()
     }
 }
 
-pub fn part2() -> i64 {
+// How evenly a hash function spreads the lenses from a sequence of operations
+struct BoxStats {
+    boxes: usize,
+    occupied_boxes: usize,
+    lenses: usize,
+    // Lenses sharing a box with another lens once every operation is done
+    collisions: usize,
+    max_chain: usize,
+    peak_chain: usize,
+    distinct_labels: usize,
+    // Distinct labels that hash to a box an earlier label already hashed to
+    label_collisions: usize,
+}
+
+fn box_stats(operations: &[LensOperation], hasher: Hasher) -> BoxStats {
+    let boxes = LensBoxes::run(operations, hasher, None);
+
+    let labels: HashSet<_> = operations.iter().map(|op| op.label()).collect();
+    let label_boxes: HashSet<_> = labels.iter().map(|label| hasher.hash(label)).collect();
+
+    let occupied: Vec<_> = boxes.boxes.iter().filter(|b| !b.is_empty()).collect();
+
+    BoxStats {
+        boxes: boxes.boxes.len(),
+        occupied_boxes: occupied.len(),
+        lenses: occupied.iter().map(|b| b.len()).sum(),
+        collisions: occupied.iter().map(|b| b.len() - 1).sum(),
+        max_chain: occupied.iter().map(|b| b.len()).max().unwrap_or(0),
+        peak_chain: boxes.peak_chain,
+        distinct_labels: labels.len(),
+        label_collisions: labels.len() - label_boxes.len(),
+    }
+}
+
+impl fmt::Display for BoxStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Boxes occupied: {} of {}", self.occupied_boxes, self.boxes)?;
+        writeln!(f, "Lenses: {} ({} sharing a box)", self.lenses, self.collisions)?;
+        writeln!(f, "Longest chain: {} at the end, {} at peak", self.max_chain, self.peak_chain)?;
+        writeln!(f, "Labels: {} ({} hashing to an already used box)", self.distinct_labels, self.label_collisions)
+    }
+}
+
+// Every non-empty box, in the puzzle's "Box 0: [rn 1] [cm 2]" format
+impl fmt::Display for LensBoxes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (box_i, b) in self.boxes.iter().enumerate().filter(|(_, b)| !b.is_empty()) {
+            let lenses = b
+                .iter()
+                .map(|(label, focal_len)| format!("[{} {}]", label, focal_len))
+                .join(" ");
+
+            writeln!(f, "Box {}: {}", box_i, lenses)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn get_operations() -> Vec<LensOperation> {
     let input_no_whitespace: String = INPUT.chars().filter(|c| !c.is_whitespace()).collect();
 
-    let operations: Vec<_> = input_no_whitespace
+    input_no_whitespace
         .split(",")
         .filter_map(|s| LensOperation::from_str(s).ok())
-        .collect();
-
-    let mut boxes: Vec<Vec<(String, i64)>> = vec![vec![]; 256];
+        .collect()
+}
 
-    operations
-        .into_iter()
-        .for_each(|op| apply_operation(&mut boxes, op));
+pub fn part2() -> i64 {
+    let operations = get_operations();
 
-    boxes
+    LensBoxes::run(&operations, Hasher::HASH, None)
+        .lens_powers()
         .iter()
-        .enumerate()
-        .map(|(box_i, b)| {
-            b.iter()
-                .enumerate()
-                .map(|(lens_i, (_, focal_len))| {
-                    (box_i + 1) as i64 * (lens_i + 1) as i64 * focal_len
-                })
-                .sum::<i64>()
-        })
+        .map(|lens| lens.power())
         .sum::<i64>()
 }
+
+// The boxes after every operation, where each label ended up, the focusing power of each lens,
+// and how well the lenses are spread between boxes
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    let operations = get_operations();
+
+    let mut out = String::new();
+    let boxes = LensBoxes::run(&operations, Hasher::HASH, Some(&mut out));
+
+    out.push_str("Labels:\n");
+    for label in operations.iter().map(|op| op.label()).unique() {
+        match boxes.locate(label) {
+            Some((box_i, slot)) => out.push_str(&format!("{}: box {}, slot {}\n", label, box_i, slot + 1)),
+            None => out.push_str(&format!("{}: removed\n", label))
+        }
+    }
+
+    let powers = boxes.lens_powers();
+
+    out.push_str("\nFocusing power:\n");
+    for lens in &powers {
+        out.push_str(&format!("{}\n", lens));
+    }
+    out.push_str(&format!("Total: {}\n", powers.iter().map(|lens| lens.power()).sum::<i64>()));
+
+    // The puzzle's hash against a couple of alternatives
+    for hasher in [Hasher::HASH, Hasher::new(31, 1009)?, Hasher::new(131, 4096)?] {
+        out.push_str(&format!("\nMultiplier {}, modulus {}:\n{}", hasher.multiplier, hasher.modulus, box_stats(&operations, hasher)));
+    }
+
+    super::print_or_write(&out, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hasher_rejects_bad_settings() {
+        assert!(Hasher::new(17, 0).is_err());
+        assert!(Hasher::new(17, -256).is_err());
+        assert!(Hasher::new(17, i64::MAX).is_err());
+        assert!(Hasher::new(0, 256).is_err());
+        assert!(Hasher::new(i64::MAX / 1000, 256).is_err());
+    }
+
+    #[test]
+    fn hasher_matches_the_puzzle() {
+        let hasher = Hasher::new(17, 256).unwrap();
+
+        assert_eq!(hasher.hash("HASH"), 52);
+        assert_eq!(hasher.hash("rn"), Hasher::HASH.hash("rn"));
+    }
+}
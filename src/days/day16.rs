@@ -1,5 +1,7 @@
 use std::collections::HashSet;
 
+use itertools::Itertools;
+
 const INPUT : &str = include_str!("input/day16.txt");
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
@@ -20,7 +22,7 @@ struct Beam {
     direction: Direction,
 }
 
-fn make_move(grid: &Vec<Vec<char>>, beam: &Beam) -> Vec<Beam> {
+fn make_move(grid: &[Vec<char>], beam: &Beam) -> Vec<Beam> {
     let mut new_pos = beam.position;
 
     match beam.direction {
@@ -77,50 +79,485 @@ fn make_move(grid: &Vec<Vec<char>>, beam: &Beam) -> Vec<Beam> {
     }
 }
 
-fn get_energized_for_beam(grid: &Vec<Vec<char>>, beam: &Beam) -> i64 {
-    let mut energized = HashSet::new();
+// Everything lit up by a single start beam
+struct Energized {
+    grid: Vec<Vec<char>>,
+    // Every beam seen, heading the way it leaves its tile
+    seen_beams: HashSet<Beam>,
+    // The beams around each loop, from the first one reached
+    loops: Vec<Vec<Beam>>
+}
+
+impl Energized {
+    fn tiles(&self) -> HashSet<(i64, i64)> {
+        self.seen_beams.iter().map(|b| b.position).collect()
+    }
+
+    fn count(&self) -> i64 {
+        self.tiles().len() as i64
+    }
+
+    // The beam directions leaving each tile
+    fn directions(&self, position: (i64, i64)) -> Vec<Direction> {
+        [Direction::Up, Direction::Down, Direction::Left, Direction::Right]
+            .into_iter()
+            .filter(|&direction| self.seen_beams.contains(&Beam { position, direction }))
+            .collect()
+    }
+
+    // Draw a character for every tile in the grid
+    fn draw(&self, tile: impl Fn((i64, i64), char) -> char) -> String {
+        let mut out = String::new();
+
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                out.push(tile((x as i64, y as i64), *c));
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    // # for energized tiles, . for the rest
+    fn energized_map(&self) -> String {
+        let tiles = self.tiles();
+
+        self.draw(|position, _| if tiles.contains(&position) { '#' } else { '.' })
+    }
+
+    // As in the puzzle: empty tiles show the beam passing over them, or how many beams if more than one
+    fn beam_map(&self) -> String {
+        self.draw(|position, c| {
+            if c != '.' {
+                return c;
+            }
+
+            match self.directions(position)[..] {
+                [] => '.',
+                [Direction::Up] => '^',
+                [Direction::Down] => 'v',
+                [Direction::Left] => '<',
+                [Direction::Right] => '>',
+                ref directions => char::from_digit(directions.len() as u32, 10).unwrap()
+            }
+        })
+    }
+
+    fn loop_report(&self) -> String {
+        self.loops.iter()
+            .map(|beams| {
+                let tiles: HashSet<_> = beams.iter().map(|b| b.position).collect();
+
+                format!("Loop of {} beams over {} tiles, entered at ({}, {}) heading {:?}\n",
+                    beams.len(), tiles.len(), beams[0].position.0, beams[0].position.1, beams[0].direction)
+            })
+            .collect()
+    }
+
+    // A plain PPM image with loops in red, other energized tiles in yellow and unlit mirrors and splitters in grey
+    fn ppm(&self) -> String {
+        let tiles = self.tiles();
+        let loop_tiles: HashSet<_> = self.loops.iter().flatten().map(|b| b.position).collect();
+
+        let mut out = format!("P3\n{} {}\n255\n", self.grid[0].len(), self.grid.len());
+
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                let position = (x as i64, y as i64);
+
+                let rgb = if loop_tiles.contains(&position) {
+                    "255 64 64"
+                } else if tiles.contains(&position) {
+                    "255 210 0"
+                } else if *c != '.' {
+                    "96 96 96"
+                } else {
+                    "16 16 16"
+                };
+
+                out.push_str(rgb);
+                out.push('\n');
+            }
+        }
+
+        out
+    }
+}
+
+// Start beams sit just outside the grid, so the first move brings them onto
+// the edge tile and through whatever mirror or splitter is there
+fn get_energized_for_beam(grid: &[Vec<char>], beam: &Beam) -> Energized {
     let mut seen_beams = HashSet::new();
+    let mut loops = Vec::new();
+
+    // Follow the beams depth first, so meeting a beam on the current path again closes a loop
+    let mut path = Vec::new();
+    let mut on_path = HashSet::new();
+    let mut next_beams = vec![make_move(grid, beam)];
+
+    while let Some(next) = next_beams.last_mut() {
+        let Some(b) = next.pop() else {
+            next_beams.pop();
+            if let Some(b) = path.pop() {
+                on_path.remove(&b);
+            }
+            continue;
+        };
 
-    let mut beams = vec![*beam];
+        if on_path.contains(&b) {
+            let start = path.iter().rposition(|p| *p == b).unwrap();
+            loops.push(path[start..].to_vec());
+        }
+        else if seen_beams.insert(b) {
+            path.push(b);
+            on_path.insert(b);
+            next_beams.push(make_move(grid, &b));
+        }
+    }
+
+    Energized {
+        grid: grid.to_vec(),
+        seen_beams,
+        loops
+    }
+}
+
+fn get_part1_energized() -> Energized {
+    get_energized_for_beam(&get_grid(INPUT), &Beam { position:(-1,0), direction: Direction::Right})
+}
+
+// The part 1 energized map, beam directions and loops on the terminal, or as a PPM image in a file
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    let energized = get_part1_energized();
+
+    match path {
+        Some(path) => std::fs::write(path, energized.ppm()).map_err(|e| e.to_string()),
+        None => {
+            print!("{}\n{}\n{}", energized.energized_map(), energized.beam_map(), energized.loop_report());
+            Ok(())
+        }
+    }
+}
+
+// Index of an in-grid beam among all tile and direction combinations
+fn beam_state(width: usize, beam: &Beam) -> usize {
+    (beam.position.1 as usize * width + beam.position.0 as usize) * 4 + beam.direction as usize
+}
+
+// Tarjan's algorithm, without recursion as a beam can wander through most of the states.
+// Returns the component of each state and the number of components, which are
+// numbered so that a component only ever leads to lower numbered ones.
+fn strongly_connected_components(succs: &[Vec<usize>]) -> (Vec<usize>, usize) {
+    const UNVISITED: usize = usize::MAX;
+
+    let mut index = vec![UNVISITED; succs.len()];
+    let mut low_link = vec![0; succs.len()];
+    let mut on_stack = vec![false; succs.len()];
+    let mut component = vec![0; succs.len()];
 
-    energized.insert(beam.position);
-    seen_beams.insert(*beam);
+    let mut next_index = 0;
+    let mut count = 0;
+    let mut stack = Vec::new();
+    // Each state being explored, with how many of its successors have been looked at
+    let mut calls: Vec<(usize, usize)> = Vec::new();
 
-    while !beams.is_empty() {
-        if let Some(beam) = beams.pop() {
-            make_move(&grid, &beam).into_iter().for_each(|b| {
-                if !seen_beams.contains(&b) {
-                    energized.insert(b.position);
-                    seen_beams.insert(b);
-                    beams.push(b);
+    for root in 0..succs.len() {
+        if index[root] != UNVISITED {
+            continue;
+        }
+
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        calls.push((root, 0));
+
+        while let Some((v, next)) = calls.last_mut() {
+            let v = *v;
+
+            if let Some(&w) = succs[v].get(*next) {
+                *next += 1;
+
+                if index[w] == UNVISITED {
+                    index[w] = next_index;
+                    low_link[w] = next_index;
+                    next_index += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low_link[v] = low_link[v].min(index[w]);
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(u, _)) = calls.last() {
+                low_link[u] = low_link[u].min(low_link[v]);
+            }
+
+            if low_link[v] == index[v] {
+                while let Some(w) = stack.pop() {
+                    on_stack[w] = false;
+                    component[w] = count;
+
+                    if w == v {
+                        break;
+                    }
                 }
-            });
+                count += 1;
+            }
         }
     }
 
-    energized.len() as i64
+    (component, count)
+}
+
+// One bit per tile of the grid
+#[derive(Clone)]
+struct TileSet {
+    bits: Vec<u64>
+}
+
+impl TileSet {
+    fn new(tiles: usize) -> Self {
+        Self { bits: vec![0; tiles.div_ceil(64)] }
+    }
+
+    fn insert(&mut self, tile: usize) {
+        self.bits[tile / 64] |= 1 << (tile % 64);
+    }
+
+    fn union_with(&mut self, other: &TileSet) {
+        self.bits.iter_mut().zip(&other.bits).for_each(|(a, b)| *a |= b);
+    }
+
+    fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+
+    fn len(&self) -> usize {
+        self.bits.iter().map(|b| b.count_ones() as usize).sum()
+    }
+}
+
+// Every beam state in the grid, condensed into strongly connected components.
+// A beam reaching any state in a component goes on to reach all of them, so the
+// components form a DAG and the tiles under a loop only need collecting once.
+struct BeamGraph {
+    width: usize,
+    tiles: usize,
+    // Component of each beam state, indexed by tile * 4 + direction
+    component: Vec<usize>,
+    // Distinct tiles covered by the states in each component
+    component_tiles: Vec<Vec<usize>>,
+    component_succs: Vec<Vec<usize>>,
+    // Everything energized from each component that contains a loop
+    coverage: Vec<Option<TileSet>>
+}
+
+impl BeamGraph {
+    fn new(grid: &[Vec<char>]) -> Self {
+        let width = grid[0].len();
+        let tiles = width * grid.len();
+
+        let succs: Vec<Vec<usize>> = (0..tiles * 4)
+            .map(|s| {
+                let beam = Beam {
+                    position: ((s / 4 % width) as i64, (s / 4 / width) as i64),
+                    direction: [Direction::Up, Direction::Down, Direction::Left, Direction::Right][s % 4]
+                };
+
+                make_move(grid, &beam).iter().map(|b| beam_state(width, b)).collect()
+            })
+            .collect();
+
+        let (component, count) = strongly_connected_components(&succs);
+
+        let mut components = vec![vec![]; count];
+        for (s, &c) in component.iter().enumerate() {
+            components[c].push(s);
+        }
+
+        let component_tiles = components.iter()
+            .map(|states| states.iter().map(|s| s / 4).sorted().dedup().collect())
+            .collect();
+
+        let component_succs: Vec<Vec<usize>> = components.iter()
+            .enumerate()
+            .map(|(c, states)| {
+                states.iter()
+                    .flat_map(|&s| succs[s].iter().map(|&t| component[t]))
+                    .filter(|&d| d != c)
+                    .sorted()
+                    .dedup()
+                    .collect()
+            })
+            .collect();
+
+        let mut graph = Self {
+            width,
+            tiles,
+            component,
+            component_tiles,
+            component_succs,
+            coverage: vec![None; components.len()]
+        };
+
+        // Components are numbered from the sinks up, so each loop can reuse the coverage of those below it
+        let mut visited = vec![0; count];
+        for (c, states) in components.iter().enumerate() {
+            if states.len() > 1 {
+                let mut covered = TileSet::new(tiles);
+                graph.collect(&[c], &mut covered, &mut visited, c + 1);
+                graph.coverage[c] = Some(covered);
+            }
+        }
+
+        graph
+    }
+
+    // Add every tile reachable from the given components, stopping at any with known coverage.
+    // Components are marked visited by setting them to `generation`, which must be new for each call.
+    fn collect(&self, starts: &[usize], covered: &mut TileSet, visited: &mut [usize], generation: usize) {
+        let mut stack = starts.to_vec();
+
+        while let Some(c) = stack.pop() {
+            if visited[c] == generation {
+                continue;
+            }
+            visited[c] = generation;
+
+            match &self.coverage[c] {
+                Some(coverage) => covered.union_with(coverage),
+                None => {
+                    self.component_tiles[c].iter().for_each(|&t| covered.insert(t));
+                    stack.extend(&self.component_succs[c]);
+                }
+            }
+        }
+    }
+
+    fn state(&self, beam: &Beam) -> usize {
+        beam_state(self.width, beam)
+    }
+}
+
+// Energize the grid from each start beam outside it in turn, sharing all the work between them
+fn get_energized_for_beams(grid: &[Vec<char>], beams: &[Beam]) -> Vec<i64> {
+    let graph = BeamGraph::new(grid);
+
+    let mut covered = TileSet::new(graph.tiles);
+    let mut visited = vec![0; graph.component_succs.len()];
+
+    beams.iter()
+        .enumerate()
+        .map(|(i, beam)| {
+            let starts: Vec<usize> = make_move(grid, beam).iter().map(|b| graph.component[graph.state(b)]).collect();
+
+            covered.clear();
+            graph.collect(&starts, &mut covered, &mut visited, i + 1);
+
+            covered.len() as i64
+        })
+        .collect()
 }
 
 pub fn part1() -> i64 {
-    let grid = get_grid(INPUT);
-    
-    get_energized_for_beam(&grid, &Beam { position:(0,0), direction: Direction::Right})
+    get_part1_energized().count()
 }
 
-pub fn part2() -> i64 {
-    let grid = get_grid(INPUT);
+// A beam heading in from just outside every edge tile
+fn get_edge_beams(grid: &[Vec<char>]) -> Vec<Beam> {
+    let width = grid[0].len() as i64;
+    let height = grid.len() as i64;
     let mut start_beams = Vec::new();
 
     // Top & Bottom
-    for i in 0..grid.get(0).unwrap().len() {
-        start_beams.push(Beam {position:(i as i64, 0), direction: Direction::Down});
-        start_beams.push(Beam {position:(i as i64, grid.len() as i64), direction: Direction::Up});
+    for i in 0..width {
+        start_beams.push(Beam {position:(i, -1), direction: Direction::Down});
+        start_beams.push(Beam {position:(i, height), direction: Direction::Up});
     }
     // Left & Right
-    for i in 0..grid.len() {
-        start_beams.push(Beam {position:(0, i as i64), direction: Direction::Right});
-        start_beams.push(Beam {position:(grid.get(0).unwrap().len() as i64,i as i64), direction: Direction::Left});
+    for i in 0..height {
+        start_beams.push(Beam {position:(-1, i), direction: Direction::Right});
+        start_beams.push(Beam {position:(width, i), direction: Direction::Left});
     }
 
-    start_beams.iter().map(|b| get_energized_for_beam(&grid, b)).max().unwrap_or(0)
-}
\ No newline at end of file
+    start_beams
+}
+
+pub fn part2() -> i64 {
+    let grid = get_grid(INPUT);
+    let start_beams = get_edge_beams(&grid);
+
+    get_energized_for_beams(&grid, &start_beams).into_iter().max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("input/day16_example.txt");
+
+    // Mirrors in every corner, with empty tiles between them
+    const CORNERS: &str = "\\./\n...\n\\./";
+
+    fn energized_from(input: &str, position: (i64, i64), direction: Direction) -> i64 {
+        get_energized_for_beam(&get_grid(input), &Beam { position, direction }).count()
+    }
+
+    #[test]
+    fn example() {
+        assert_eq!(energized_from(EXAMPLE, (-1, 0), Direction::Right), 46);
+
+        let grid = get_grid(EXAMPLE);
+        assert_eq!(get_energized_for_beams(&grid, &get_edge_beams(&grid)).into_iter().max(), Some(51));
+    }
+
+    #[test]
+    fn first_tile_mirror_turns_the_beam() {
+        // Turned down by the top left mirror, then round the bottom and up the right side
+        assert_eq!(energized_from(CORNERS, (-1, 0), Direction::Right), 7);
+        assert_eq!(energized_from("\\..\n...", (-1, 0), Direction::Right), 2);
+        assert_eq!(energized_from("|..\n...", (-1, 0), Direction::Right), 2);
+    }
+
+    #[test]
+    fn bottom_and_right_starts_enter_the_edge_tile() {
+        // Straight back out of the grid through the neighbouring edge
+        assert_eq!(energized_from(CORNERS, (3, 2), Direction::Left), 1);
+        assert_eq!(energized_from(CORNERS, (2, 3), Direction::Up), 1);
+        assert_eq!(energized_from(CORNERS, (0, 3), Direction::Up), 1);
+
+        assert_eq!(energized_from(CORNERS, (1, 3), Direction::Up), 3);
+        assert_eq!(energized_from(CORNERS, (3, 1), Direction::Left), 3);
+    }
+
+    #[test]
+    fn edge_beams_start_outside_the_grid() {
+        let grid = get_grid(CORNERS);
+        let beams = get_edge_beams(&grid);
+
+        assert_eq!(beams.len(), 12);
+
+        for beam in &beams {
+            let (x, y) = beam.position;
+            assert!(!(0..3).contains(&x) || !(0..3).contains(&y));
+            assert_eq!(make_move(&grid, beam).len(), 1);
+        }
+    }
+
+    #[test]
+    fn shared_coverage_matches_single_beams() {
+        for input in [EXAMPLE, CORNERS] {
+            let grid = get_grid(input);
+            let beams = get_edge_beams(&grid);
+
+            let single: Vec<_> = beams.iter().map(|b| get_energized_for_beam(&grid, b).count()).collect();
+            assert_eq!(get_energized_for_beams(&grid, &beams), single);
+        }
+    }
+}
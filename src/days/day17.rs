@@ -1,3 +1,5 @@
+use std::fmt;
+
 use pathfinding::prelude::dijkstra;
 
 const INPUT: &str = include_str!("input/day17.txt");
@@ -16,7 +18,7 @@ fn get_grid(input: &str) -> Vec<Vec<usize>> {
 fn move_pos_in_direction(
     pos: (usize, usize),
     dir: Direction,
-    grid: &Vec<Vec<usize>>
+    grid: &[Vec<usize>]
 ) -> Option<(usize, usize)> {
     if pos.0 == 0 && dir == Direction::Left
         || pos.1 == 0 && dir == Direction::Up
@@ -62,7 +64,7 @@ struct CruciblePos {
 }
 
 impl CruciblePos {
-    fn successors(&self, grid:&Vec<Vec<usize>>, ultra: bool) -> Vec<(CruciblePos, usize)> {
+    fn successors(&self, grid: &[Vec<usize>], ultra: bool) -> Vec<(CruciblePos, usize)> {
         let mut dirs = Vec::new();
 
         if !ultra && self.dir != Direction::None {
@@ -107,30 +109,122 @@ impl CruciblePos {
     }
 }
 
-pub fn part1() -> i64 {
-    let grid = get_grid(INPUT);
+// Every crucible position from the start to the factory, and the heat lost along the way
+struct Route {
+    steps: Vec<CruciblePos>,
+    heat_loss: usize
+}
+
+// A run of moves in the same direction
+struct Segment {
+    dir: Direction,
+    // The tile the segment turns off from
+    from: (usize, usize),
+    length: usize,
+    heat_loss: usize
+}
 
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {} from ({}, {}), heat loss {}", self.dir, self.length, self.from.0, self.from.1, self.heat_loss)
+    }
+}
+
+impl Route {
+    fn segments(&self, grid: &[Vec<usize>]) -> Vec<Segment> {
+        let mut segments: Vec<Segment> = Vec::new();
+
+        for (prev, step) in self.steps.iter().zip(&self.steps[1..]) {
+            let heat_loss = grid[step.pos.1][step.pos.0];
+
+            match segments.last_mut() {
+                Some(segment) if step.straight > 1 => {
+                    segment.length += 1;
+                    segment.heat_loss += heat_loss;
+                },
+                _ => segments.push(Segment { dir: step.dir, from: prev.pos, length: 1, heat_loss })
+            }
+        }
+
+        segments
+    }
+
+    // The heat loss grid with an arrow on each tile the crucible moves onto
+    fn render(&self, grid: &[Vec<usize>]) -> String {
+        let mut tiles: Vec<Vec<char>> = grid.iter()
+            .map(|row| row.iter().map(|h| char::from_digit(*h as u32, 10).unwrap_or('?')).collect())
+            .collect();
+
+        for step in &self.steps[1..] {
+            tiles[step.pos.1][step.pos.0] = match step.dir {
+                Direction::Up => '^',
+                Direction::Right => '>',
+                Direction::Left => '<',
+                Direction::Down => 'v',
+                Direction::None => continue
+            };
+        }
+
+        tiles.iter().map(|row| row.iter().collect::<String>() + "\n").collect()
+    }
+}
+
+fn find_route(grid: &[Vec<usize>], ultra: bool) -> Option<Route> {
     let start = CruciblePos {
         pos: (0,0),
         dir: Direction::None,
         straight: 0
     };
 
-    let result = dijkstra(&start, |p| p.successors(&grid, false), |p| p.pos == (grid.len()-1, grid[0].len()-1));
+    let end = (grid[0].len()-1, grid.len()-1);
+    let min_straight = if ultra { 4 } else { 0 };
+
+    let (steps, heat_loss) = dijkstra(&start, |p| p.successors(grid, ultra), |p| p.pos == end && p.straight >= min_straight)?;
+
+    let route = Route { steps, heat_loss };
+
+    if cfg!(debug_assertions) {
+        let segments = route.segments(grid);
+        let (shortest, longest) = if ultra { (4, 10) } else { (1, 3) };
 
-    result.unwrap().1 as i64
+        debug_assert_eq!(segments.iter().map(|s| s.heat_loss).sum::<usize>(), route.heat_loss);
+        debug_assert!(segments.iter().all(|s| (shortest..=longest).contains(&s.length)));
+        debug_assert!(segments.windows(2).all(|w| w[1].dir != w[0].dir && w[1].dir.get_rotated().contains(&w[0].dir)));
+    }
+
+    Some(route)
 }
 
-pub fn part2() -> i64 {
+// The route for each part drawn over the heat loss grid, with the heat lost on every straight run
+pub fn report(path: Option<&str>) -> Result<(), String> {
     let grid = get_grid(INPUT);
-    
-    let start = CruciblePos {
-        pos: (0,0),
-        dir: Direction::None,
-        straight: 0
-    };
+    let mut out = String::new();
+
+    for (part, ultra) in [(1, false), (2, true)] {
+        let Some(route) = find_route(&grid, ultra) else {
+            return Err(format!("No route to the factory in part {}", part));
+        };
+
+        out.push_str(&format!("Part {}:\n{}", part, route.render(&grid)));
+
+        for segment in route.segments(&grid) {
+            out.push_str(&format!("{}\n", segment));
+        }
+
+        out.push_str(&format!("Total heat loss {}\n\n", route.heat_loss));
+    }
+
+    super::print_or_write(&out, path)
+}
+
+pub fn part1() -> i64 {
+    let grid = get_grid(INPUT);
+
+    find_route(&grid, false).map_or(-1, |r| r.heat_loss as i64)
+}
 
-    let result = dijkstra(&start, |p| p.successors(&grid, true), |p| p.pos == (grid.len()-1, grid[0].len()-1) && p.straight >= 4);
+pub fn part2() -> i64 {
+    let grid = get_grid(INPUT);
 
-    result.unwrap().1 as i64
+    find_route(&grid, true).map_or(-1, |r| r.heat_loss as i64)
 }
@@ -1,79 +1,97 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+const INPUT: &str = include_str!("input/day19.txt");
+
+#[derive(Clone, Copy, PartialEq)]
+enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+impl Operator {
+    // The inclusive range of values that satisfy `value <op> rhs`
+    fn matching(&self, rhs: i64) -> (i64, i64) {
+        match self {
+            Operator::Less => (i64::MIN, rhs - 1),
+            Operator::LessOrEqual => (i64::MIN, rhs),
+            Operator::Greater => (rhs + 1, i64::MAX),
+            Operator::GreaterOrEqual => (rhs, i64::MAX),
+            Operator::Equal => (rhs, rhs),
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+            Operator::Equal => "=",
+        };
 
-const INPUT: &str = include_str!("input/day19_example.txt");
+        write!(f, "{}", s)
+    }
+}
 
 struct Rule {
     prop: String,
-    operator: char,
+    operator: Operator,
     value: i64,
     dest: String,
 }
 
 impl Rule {
+    fn condition(&self) -> String {
+        format!("{}{}{}", self.prop, self.operator, self.value)
+    }
+
     fn eval(&self, input: &Item) -> Option<String> {
-        let Some(input_value) = input.props.get(&self.prop) else {
-            return None;
-        };
+        let input_value = input.props.get(&self.prop)?;
 
-        match self.operator {
-            '>' => {
-                if *input_value > self.value {
-                    Some(self.dest.clone())
-                } else {
-                    None
-                }
-            }
-            '<' => {
-                if *input_value < self.value {
-                    Some(self.dest.clone())
-                } else {
-                    None
-                }
-            }
-            _ => panic!("Invalid oprator in rule"),
+        let (min_val, max_val) = self.operator.matching(self.value);
+
+        if (min_val..=max_val).contains(input_value) {
+            Some(self.dest.clone())
+        } else {
+            None
         }
     }
 
-    fn eval_range(
-        &self,
-        item_range: &ItemRange,
-    ) -> (Option<ItemRange>, Option<(String, ItemRange)>) {
+    // Split the range into the parts this rule doesn't match and the part it sends to its destination
+    fn eval_range(&self, item_range: &ItemRange) -> (Vec<ItemRange>, Option<(String, ItemRange)>) {
         let min_val = item_range.prop_mins[&self.prop];
         let max_val = item_range.prop_maxs[&self.prop];
 
-        match self.operator {
-            '>' => {
-                if min_val > self.value {
-                    (None, Some((self.dest.clone(), item_range.clone())))
-                } else if max_val > self.value {
-                    let mut mapped = item_range.clone();
-                    let mut unmapped = item_range.clone();
+        let (match_min, match_max) = self.operator.matching(self.value);
+        let mapped_min = min_val.max(match_min);
+        let mapped_max = max_val.min(match_max);
 
-                    *unmapped.prop_maxs.get_mut(&self.prop).unwrap() = self.value;
-                    *mapped.prop_mins.get_mut(&self.prop).unwrap() = self.value + 1;
+        if mapped_min > mapped_max {
+            return (vec![item_range.clone()], None);
+        }
 
-                    (Some(unmapped), Some((self.dest.clone(), mapped)))
-                } else {
-                    (Some(item_range.clone()), None)
-                }
-            }
-            '<' => {
-                if max_val < self.value {
-                    (None, Some((self.dest.clone(), item_range.clone())))
-                } else if min_val < self.value {
-                    let mut mapped = item_range.clone();
-                    let mut unmapped = item_range.clone();
+        let mut unmapped = Vec::new();
 
-                    *unmapped.prop_mins.get_mut(&self.prop).unwrap() = self.value;
-                    *mapped.prop_maxs.get_mut(&self.prop).unwrap() = self.value - 1;
+        if min_val < mapped_min {
+            unmapped.push(item_range.with_bounds(&self.prop, min_val, mapped_min - 1));
+        }
 
-                    (Some(unmapped), Some((self.dest.clone(), mapped)))
-                } else {
-                    (Some(item_range.clone()), None)
-                }
-            }
-            _ => panic!("Invalid oprator in rule"),
+        if mapped_max < max_val {
+            unmapped.push(item_range.with_bounds(&self.prop, mapped_max + 1, max_val));
         }
+
+        let mapped = item_range.with_bounds(&self.prop, mapped_min, mapped_max);
+
+        (unmapped, Some((self.dest.clone(), mapped)))
     }
 }
 
@@ -81,26 +99,40 @@ impl FromStr for Rule {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let operator = if s.contains('<') {
-            '<'
-        } else if s.contains('>') {
-            '>'
-        } else {
+        let Some(op_start) = s.find(['<', '>', '=']) else {
             return Err(format!("Failed to find operator for {}", s));
         };
 
-        let Some((prop, rhs)) = s.split_once(operator) else {
-            return Err(format!("Failed to find property for rule {}", s));
+        let (prop, rhs) = s.split_at(op_start);
+
+        let (operator, rhs) = if let Some(rhs) = rhs.strip_prefix("<=") {
+            (Operator::LessOrEqual, rhs)
+        } else if let Some(rhs) = rhs.strip_prefix(">=") {
+            (Operator::GreaterOrEqual, rhs)
+        } else if let Some(rhs) = rhs.strip_prefix('<') {
+            (Operator::Less, rhs)
+        } else if let Some(rhs) = rhs.strip_prefix('>') {
+            (Operator::Greater, rhs)
+        } else {
+            (Operator::Equal, &rhs[1..])
         };
 
         let Some((val, dest)) = rhs.split_once(':') else {
             return Err(format!("Failed to find value/destination for rule {}", s));
         };
 
+        let Ok(value) = val.parse::<i64>() else {
+            return Err(format!("Failed to parse value as i64 in rule {}", s));
+        };
+
+        if prop.is_empty() || dest.is_empty() {
+            return Err(format!("Empty property or destination in rule {}", s));
+        }
+
         Ok(Rule {
             prop: prop.to_string(),
-            operator: operator,
-            value: val.parse::<i64>().unwrap(),
+            operator,
+            value,
             dest: dest.to_string(),
         })
     }
@@ -126,23 +158,72 @@ impl Workflow {
     }
 
     fn apply_range(&self, item_range: &ItemRange) -> Vec<(String, ItemRange)> {
-        let mut unmapped = item_range.clone();
+        let mut unmapped = vec![item_range.clone()];
 
         let mut mappings = Vec::new();
 
         for rule in &self.rule_map {
-            let (new_unmapped, mapping) = rule.eval_range(&unmapped);
+            let mut new_unmapped = Vec::new();
+
+            for remaining in &unmapped {
+                let (remaining, mapping) = rule.eval_range(remaining);
 
-            if let Some(new_unmapped) = new_unmapped {
-                unmapped = new_unmapped;
+                new_unmapped.extend(remaining);
+
+                if let Some(mapping) = mapping {
+                    mappings.push(mapping);
+                }
             }
 
-            if let Some(mapping) = mapping {
-                mappings.push(mapping);
+            unmapped = new_unmapped;
+        }
+
+        // Whatever no rule claimed goes to the fallthrough
+        for remaining in unmapped {
+            mappings.push((self.fallthrough.clone(), remaining));
+        }
+
+        mappings
+    }
+
+    // Drop rules that can never fire or that send to the same place as the fallthrough would.
+    // Returns whether any rules were removed.
+    fn prune_rules(&mut self, start: &ItemRange) -> bool {
+        let rules = std::mem::take(&mut self.rule_map);
+        let rule_count = rules.len();
+
+        let mut remaining = vec![start.clone()];
+
+        for rule in rules {
+            let mut unmapped = Vec::new();
+            let mut fires = false;
+
+            for range in &remaining {
+                let (range_unmapped, mapped) = rule.eval_range(range);
+
+                unmapped.extend(range_unmapped);
+                fires |= mapped.is_some();
+            }
+
+            if !fires {
+                continue;
             }
+
+            if unmapped.is_empty() {
+                // This rule always fires, so everything after it is unreachable
+                self.fallthrough = rule.dest;
+                break;
+            }
+
+            remaining = unmapped;
+            self.rule_map.push(rule);
+        }
+
+        while self.rule_map.last().is_some_and(|r| r.dest == self.fallthrough) {
+            self.rule_map.pop();
         }
 
-        return mappings;
+        self.rule_map.len() != rule_count
     }
 }
 
@@ -154,23 +235,315 @@ impl FromStr for Workflow {
             return Err(format!("Failed to find name in {}", s));
         };
 
-        let rhs = rhs.replace('}', "");
+        let Some(rhs) = rhs.strip_suffix('}') else {
+            return Err(format!("Failed to find closing brace in {}", s));
+        };
+
+        if name.is_empty() {
+            return Err(format!("Empty workflow name in {}", s));
+        }
 
         let mut rules = rhs.split(',');
-        let Some(fallthrough) = rules.next_back() else {
+        let Some(fallthrough) = rules.next_back().filter(|f| !f.is_empty() && !f.contains(':')) else {
             return Err(format!("Failed to find fallthrough value for {}", s));
         };
 
-        let rule_map = rules.filter_map(|r_s| Rule::from_str(r_s).ok()).collect();
+        let rule_map = rules
+            .map(Rule::from_str)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Workflow {}: {}", name, e))?;
 
         Ok(Self {
             name: name.to_string(),
-            rule_map: rule_map,
+            rule_map,
             fallthrough: fallthrough.to_string(),
         })
     }
 }
 
+struct WorkflowSet {
+    workflows: Vec<Workflow>,
+    index: HashMap<String, usize>,
+}
+
+impl WorkflowSet {
+    fn get(&self, name: &str) -> &Workflow {
+        &self.workflows[self.index[name]]
+    }
+
+    fn destinations(workflow: &Workflow) -> impl Iterator<Item = &str> {
+        workflow
+            .rule_map
+            .iter()
+            .map(|r| r.dest.as_str())
+            .chain(std::iter::once(workflow.fallthrough.as_str()))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.index.contains_key("in") {
+            return Err("No \"in\" workflow found".to_string());
+        }
+
+        for wf in &self.workflows {
+            if let Some(dest) = Self::destinations(wf)
+                .find(|d| *d != "A" && *d != "R" && !self.index.contains_key(*d))
+            {
+                return Err(format!("Workflow {} sends to unknown destination {}", wf.name, dest));
+            }
+        }
+
+        // Depth first search, colouring each workflow as unvisited (0), on the stack (1) or done (2)
+        let mut state = vec![0u8; self.workflows.len()];
+
+        for root in 0..self.workflows.len() {
+            if state[root] != 0 {
+                continue;
+            }
+
+            state[root] = 1;
+            let mut stack = vec![(root, Self::destinations(&self.workflows[root]))];
+
+            while let Some((wf_i, dests)) = stack.last_mut() {
+                let wf_i = *wf_i;
+
+                let Some(dest) = dests.next() else {
+                    state[wf_i] = 2;
+                    stack.pop();
+                    continue;
+                };
+
+                let Some(&dest_i) = self.index.get(dest) else {
+                    continue;
+                };
+
+                match state[dest_i] {
+                    0 => {
+                        state[dest_i] = 1;
+                        stack.push((dest_i, Self::destinations(&self.workflows[dest_i])));
+                    }
+                    1 => {
+                        return Err(format!(
+                            "Cycle found between workflows {} and {}",
+                            self.workflows[wf_i].name, dest
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rule_count(&self) -> usize {
+        self.workflows.iter().map(|wf| wf.rule_map.len()).sum()
+    }
+
+    fn reindex(&mut self) {
+        self.index = self
+            .workflows
+            .iter()
+            .enumerate()
+            .map(|(i, wf)| (wf.name.clone(), i))
+            .collect();
+    }
+
+    // Simplify the workflows without changing which items are accepted, returning the number of rules removed
+    fn simplify(&mut self, start: &ItemRange) -> usize {
+        let start_rule_count = self.rule_count();
+
+        loop {
+            let mut changed = false;
+
+            for wf in &mut self.workflows {
+                changed |= wf.prune_rules(start);
+            }
+
+            // Any workflow left with only a fallthrough can be replaced by its fallthrough
+            let collapsed: HashMap<String, String> = self
+                .workflows
+                .iter()
+                .filter(|wf| wf.rule_map.is_empty() && wf.name != "in")
+                .map(|wf| (wf.name.clone(), wf.fallthrough.clone()))
+                .collect();
+
+            if !collapsed.is_empty() {
+                let resolve = |dest: &mut String| {
+                    while let Some(new_dest) = collapsed.get(dest) {
+                        *dest = new_dest.clone();
+                    }
+                };
+
+                for wf in &mut self.workflows {
+                    wf.rule_map.iter_mut().for_each(|rule| resolve(&mut rule.dest));
+                    resolve(&mut wf.fallthrough);
+                }
+
+                self.workflows.retain(|wf| !collapsed.contains_key(&wf.name));
+                self.reindex();
+                changed = true;
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        // Remove any workflows that can't be reached from "in"
+        let mut reachable = HashSet::from(["in".to_string()]);
+        let mut to_visit = vec!["in".to_string()];
+
+        while let Some(name) = to_visit.pop() {
+            for dest in Self::destinations(self.get(&name)) {
+                if self.index.contains_key(dest) && reachable.insert(dest.to_string()) {
+                    to_visit.push(dest.to_string());
+                }
+            }
+        }
+
+        self.workflows.retain(|wf| reachable.contains(&wf.name));
+        self.reindex();
+
+        start_rule_count - self.rule_count()
+    }
+
+    // The workflows an item passes through, ending in "A" or "R"
+    fn trace(&self, item: &Item) -> Vec<String> {
+        let mut path = vec!["in".to_string()];
+
+        while let Some(wf_name) = path.last().filter(|n| *n != "A" && *n != "R") {
+            path.push(self.get(wf_name).apply(item));
+        }
+
+        path
+    }
+
+    // The range of every property items have or the workflows use, plus any extra properties given bounds.
+    // Properties without explicit bounds use the default.
+    fn start_range(&self, item_props: &HashSet<String>, default: (i64, i64), bounds: &HashMap<String, (i64, i64)>) -> ItemRange {
+        let props: HashSet<&str> = self
+            .workflows
+            .iter()
+            .flat_map(|wf| wf.rule_map.iter().map(|r| r.prop.as_str()))
+            .chain(item_props.iter().map(|p| p.as_str()))
+            .chain(bounds.keys().map(|k| k.as_str()))
+            .collect();
+
+        let mut range = ItemRange {
+            prop_mins: HashMap::new(),
+            prop_maxs: HashMap::new(),
+        };
+
+        for prop in props {
+            let (min, max) = bounds.get(prop).copied().unwrap_or(default);
+
+            range.prop_mins.insert(prop.to_string(), min);
+            range.prop_maxs.insert(prop.to_string(), max);
+        }
+
+        range
+    }
+
+    fn accepted_regions(&self, start: &ItemRange) -> Vec<AcceptedRegion> {
+        let mut to_process = vec![(vec!["in".to_string()], start.clone())];
+
+        let mut accepted = Vec::new();
+
+        while let Some((path, item_range)) = to_process.pop() {
+            let wf = self.get(path.last().unwrap());
+
+            for (dest, range) in wf.apply_range(&item_range) {
+                if dest == "R" {
+                    continue;
+                }
+
+                let mut dest_path = path.clone();
+                dest_path.push(dest.clone());
+
+                if dest == "A" {
+                    accepted.push(AcceptedRegion { range, path: dest_path });
+                } else {
+                    to_process.push((dest_path, range));
+                }
+            }
+        }
+
+        accepted
+    }
+
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph workflows {\n");
+
+        dot.push_str("    \"A\" [shape=doublecircle, color=green];\n");
+        dot.push_str("    \"R\" [shape=doublecircle, color=red];\n");
+
+        for wf in &self.workflows {
+            for rule in &wf.rule_map {
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    wf.name,
+                    rule.dest,
+                    rule.condition()
+                ));
+            }
+
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\" [label=\"else\", style=dashed];\n",
+                wf.name, wf.fallthrough
+            ));
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+impl FromStr for WorkflowSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let workflows = s
+            .lines()
+            .map(Workflow::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut index = HashMap::new();
+
+        for (i, wf) in workflows.iter().enumerate() {
+            if index.insert(wf.name.clone(), i).is_some() {
+                return Err(format!("Duplicate workflow {}", wf.name));
+            }
+        }
+
+        let set = Self { workflows, index };
+
+        set.validate()?;
+
+        Ok(set)
+    }
+}
+
+// Every property any item has, including those no rule tests
+fn get_item_props(items: &str) -> HashSet<String> {
+    items
+        .lines()
+        .filter_map(|l| Item::from_str(l.trim()).ok())
+        .flat_map(|item| item.props.into_keys())
+        .collect()
+}
+
+fn get_sections() -> (WorkflowSet, &'static str) {
+    let Some((workflows, items)) = INPUT.split_once("\n\n").or_else(|| INPUT.split_once("\r\n\r\n")) else {
+        panic!("Failed to find sections");
+    };
+
+    match WorkflowSet::from_str(workflows) {
+        Ok(workflows) => (workflows, items),
+        Err(e) => panic!("Invalid workflows: {}", e),
+    }
+}
+
 #[derive(Clone)]
 struct Item {
     props: HashMap<String, i64>,
@@ -217,134 +590,112 @@ struct ItemRange {
 }
 
 impl ItemRange {
-    fn start_range() -> Self {
-        Self { 
-            prop_mins: HashMap::from([
-                ("x".to_string(), 1),
-                ("m".to_string(), 1),
-                ("a".to_string(), 1),
-                ("s".to_string(), 1),
-            ]), 
-            prop_maxs: HashMap::from([
-                ("x".to_string(), 4000),
-                ("m".to_string(), 4000),
-                ("a".to_string(), 4000),
-                ("s".to_string(), 4000),
-            ]), 
-        }
+    fn with_bounds(&self, prop: &str, min: i64, max: i64) -> Self {
+        let mut range = self.clone();
+
+        range.prop_mins.insert(prop.to_string(), min);
+        range.prop_maxs.insert(prop.to_string(), max);
+
+        range
+    }
+
+    fn volume(&self) -> i64 {
+        self.prop_mins
+            .iter()
+            .map(|(prop, min)| self.prop_maxs[prop] - min + 1)
+            .product()
     }
 }
 
-pub fn part1() -> i64 {
-    let Some((workflows, items)) = INPUT.split_once("\r\n\r\n") else {
-        panic!("Failed to find sections");
-    };
+impl fmt::Display for ItemRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut props: Vec<_> = self.prop_mins.keys().collect();
+        props.sort();
 
-    let workflows: Vec<_> = workflows
-        .lines()
-        .filter_map(|l| Workflow::from_str(l).ok())
-        .collect();
-
-    let mut bins = vec![Vec::<Item>::new(); workflows.len()];
+        let bounds: Vec<_> = props
+            .iter()
+            .map(|p| format!("{}={}..={}", p, self.prop_mins[*p], self.prop_maxs[*p]))
+            .collect();
 
-    let bin_map: HashMap<&str, usize> = workflows
-        .iter()
-        .enumerate()
-        .map(|(i, w)| (w.name.as_str(), i))
-        .collect();
+        write!(f, "{{{}}}", bounds.join(","))
+    }
+}
 
-    let start_bin_i = *bin_map.get("in").unwrap();
+struct AcceptedRegion {
+    range: ItemRange,
+    path: Vec<String>,
+}
 
-    for item in items.lines().filter_map(|l| Item::from_str(l).ok()) {
-        bins[start_bin_i].push(item);
+impl fmt::Display for AcceptedRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.range, self.path.join(" -> "))
     }
+}
 
-    let mut accepted = Vec::new();
+pub fn part1() -> i64 {
+    let (workflows, items) = get_sections();
 
-    while let Some(wf_i) = bins.iter_mut().enumerate().position(|(_, b)| !b.is_empty()) {
-        let Some(item) = bins[wf_i].pop() else {
-            panic!("No item found");
-        };
-        let wf = &workflows[wf_i];
+    items
+        .lines()
+        .filter_map(|l| Item::from_str(l.trim()).ok())
+        .filter(|item| workflows.trace(item).last().is_some_and(|dest| dest == "A"))
+        .map(|item| item.props.values().sum::<i64>())
+        .sum::<i64>()
+}
 
-        let dest = wf.apply(&item);
+pub fn part2() -> i64 {
+    let (mut workflows, items) = get_sections();
 
-        if dest == "R" {
-            continue;
-        } else if dest == "A" {
-            accepted.push(item);
-        } else {
-            let dest_i = bin_map[dest.as_str()];
+    let start = workflows.start_range(&get_item_props(items), (1, 4000), &HashMap::new());
 
-            bins[dest_i].push(item);
-        }
-    }
+    workflows.simplify(&start);
 
-    accepted
+    workflows
+        .accepted_regions(&start)
         .iter()
-        .map(|i| i.props.values().sum::<i64>())
+        .map(|region| region.range.volume())
         .sum::<i64>()
 }
 
-pub fn part2() -> i64 {
-    let Some((workflows, _)) = INPUT.split_once("\r\n\r\n") else {
-        panic!("Failed to find sections");
-    };
+// The path each part takes and every accepted region through the workflows as written,
+// then how many rules simplifying removes and the simplified workflows as a Graphviz graph
+pub fn report(path: Option<&str>) -> Result<(), String> {
+    let (mut workflows, items) = get_sections();
 
-    let workflows: Vec<_> = workflows
-        .lines()
-        .filter_map(|l| Workflow::from_str(l).ok())
-        .collect();
+    let mut out = String::from("Parts:\n");
 
-    let mut bins = vec![Vec::<ItemRange>::new(); workflows.len()];
+    for line in items.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        let item = Item::from_str(line)?;
+        out.push_str(&format!("{}: {}\n", line, workflows.trace(&item).join(" -> ")));
+    }
 
-    let bin_map: HashMap<&str, usize> = workflows
-        .iter()
-        .enumerate()
-        .map(|(i, w)| (w.name.as_str(), i))
-        .collect();
+    let start = workflows.start_range(&get_item_props(items), (1, 4000), &HashMap::new());
 
-    let start_bin_i = *bin_map.get("in").unwrap();
+    out.push_str("\nAccepted regions:\n");
+    for region in workflows.accepted_regions(&start) {
+        out.push_str(&format!("{}\n", region));
+    }
 
-    bins[start_bin_i].push(ItemRange::start_range());
+    let rule_count = workflows.rule_count();
+    let removed = workflows.simplify(&start);
 
-    let mut accepted = Vec::new();
+    out.push_str(&format!("\nSimplifying removed {} of {} rules\n{}", removed, rule_count, workflows.to_dot()));
 
-    while let Some(wf_i) = bins.iter_mut().enumerate().position(|(_, b)| !b.is_empty()) {
-        let Some(item_range) = bins[wf_i].pop() else {
-            panic!("No item found");
-        };
-        let wf = &workflows[wf_i];
+    super::print_or_write(&out, path)
+}
 
-        for (dest, range) in wf.apply_range(&item_range) {
-            if dest == "R" {
-                continue;
-            } else if dest == "A" {
-                accepted.push(range);
-            } else {
-                let dest_i = bin_map[dest.as_str()];
-                
-                bins[dest_i].push(range);
-            }
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    accepted.iter().map(|range| {
-        let min_x = range.prop_mins["x"];
-        let max_x = range.prop_maxs["x"];
-        let min_m = range.prop_mins["m"];
-        let max_m = range.prop_maxs["m"];
-        let min_a = range.prop_mins["a"];
-        let max_a = range.prop_maxs["a"];
-        let min_s = range.prop_mins["s"];
-        let max_s = range.prop_maxs["s"];
-
-        let x_range = max_x - min_x + 1;
-        let m_range = max_m - min_m + 1;
-        let a_range = max_a - min_a + 1;
-        let s_range = max_s - min_s + 1;
-
-        x_range * m_range * a_range * s_range
-    })
-    .sum::<i64>()
+    #[test]
+    fn untested_properties_are_counted() {
+        let workflows = WorkflowSet::from_str("in{x>10:A,R}").unwrap();
+        let item_props = get_item_props("{x=1,m=2,a=3,s=4}");
+
+        let start = workflows.start_range(&item_props, (1, 4000), &HashMap::new());
+        let accepted: i64 = workflows.accepted_regions(&start).iter().map(|r| r.range.volume()).sum();
+
+        assert_eq!(accepted, 3990 * 4000 * 4000 * 4000);
+    }
 }
@@ -1,24 +1,35 @@
 use std::time::Instant;
 
+mod cycle;
 mod days;
 
 use days::*;
 
+// Extra output about how a day was solved, given an optional file to write it to
+type Report = fn(Option<&str>) -> Result<(), String>;
+
 #[derive(Clone)]
 struct Day {
     name: String,
     part1: fn() -> i64,
-    part2: fn() -> i64
+    part2: fn() -> i64,
+    report: Option<Report>
 }
 
 impl Day {
     fn new(name: &str, part1: fn()->i64, part2: fn()->i64) -> Day {
         Day { 
             name: name.to_string(),
-            part1: part1, 
-            part2: part2
+            part1,
+            part2,
+            report: None
         }
     }
+
+    fn with_report(mut self, report: Report) -> Day {
+        self.report = Some(report);
+        self
+    }
 }
 
 struct DayRunner {
@@ -28,7 +39,7 @@ struct DayRunner {
 impl DayRunner {
     fn new(day: Day) -> DayRunner {
         DayRunner { 
-            day: day
+            day
         }
     }
 
@@ -54,21 +65,21 @@ fn main()
         Day::new("Day 2", day2_part1,  day2_part2),
         Day::new("Day 3", day3_part1,  day3_part2),
         Day::new("Day 4", day4_part1,  day4_part2),
-        Day::new("Day 5", day5_part1,  day5_part2),
+        Day::new("Day 5", day5_part1,  day5_part2).with_report(day5_report),
         Day::new("Day 6", day6_part1,  day6_part2),
-        Day::new("Day 7", day7_part1,  day7_part2),
+        Day::new("Day 7", day7_part1,  day7_part2).with_report(day7_report),
         Day::new("Day 8", day8_part1,  day8_part2),
         Day::new("Day 9", day9_part1,  day9_part2),
-        Day::new("Day 10", day10_part1, day10_part2),
-        Day::new("Day 11", day11_part1, day11_part2),
-        Day::new("Day 12", day12_part1, day12_part2),
-        Day::new("Day 13", day13_part1, day13_part2),
+        Day::new("Day 10", day10_part1, day10_part2).with_report(day10_report),
+        Day::new("Day 11", day11_part1, day11_part2).with_report(day11_report),
+        Day::new("Day 12", day12_part1, day12_part2).with_report(day12_report),
+        Day::new("Day 13", day13_part1, day13_part2).with_report(day13_report),
         Day::new("Day 14", day14_part1, day14_part2),
-        Day::new("Day 15", day15_part1, day15_part2),
-        Day::new("Day 16", day16_part1, day16_part2),
-        Day::new("Day 17", day17_part1, day17_part2),
+        Day::new("Day 15", day15_part1, day15_part2).with_report(day15_report),
+        Day::new("Day 16", day16_part1, day16_part2).with_report(day16_report),
+        Day::new("Day 17", day17_part1, day17_part2).with_report(day17_report),
         Day::new("Day 18", day18_part1, day18_part2),
-        Day::new("Day 19", day19_part1, day19_part2),
+        Day::new("Day 19", day19_part1, day19_part2).with_report(day19_report),
         Day::new("Day 20", day20_part1, day20_part2),
         Day::new("Day 21", day21_part1, day21_part2),
         Day::new("Day 22", day22_part1, day22_part2),
@@ -80,7 +91,18 @@ fn main()
     if args.len() > 1 {
         let day_i = args[1].parse::<usize>().unwrap() - 1;
         
-        if let Some(day) = days.get(day_i) {
+        // e.g. `16 report` or `16 report out.ppm`
+        if args.get(2).is_some_and(|a| a == "report") {
+            match days.get(day_i).and_then(|day| day.report) {
+                Some(report) => {
+                    if let Err(e) = report(args.get(3).map(|a| a.as_str())) {
+                        println!("ERROR: {}", e);
+                    }
+                }
+                None => println!("No report for this day")
+            }
+        }
+        else if let Some(day) = days.get(day_i) {
             DayRunner::new(day.clone()).run();
         }
         else {
@@ -92,7 +114,7 @@ fn main()
     }
 }
 
-fn run_all_days(days: &Vec<Day>) {
+fn run_all_days(days: &[Day]) {
     days.iter().for_each(|day| {
         DayRunner::new(day.clone()).run();
     });